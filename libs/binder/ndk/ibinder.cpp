@@ -623,6 +623,28 @@ void* AIBinder_getUserData(AIBinder* binder) {
     return bBinder->getUserData();
 }
 
+static binder_status_t WriteInterfaceHeader(const AIBinder_Class* clazz, AParcel* in) {
+    status_t status = android::OK;
+    if (clazz->writeHeader) {
+        status = in->get()->writeInterfaceToken(clazz->getInterfaceDescriptor());
+    }
+    return PruneStatusT(status);
+}
+
+static binder_status_t WriteTransactionHeader(AIBinder* binder, const AIBinder_Class* clazz,
+                                              AParcel* in) {
+    in->get()->markForBinder(binder->getBinder());
+    return WriteInterfaceHeader(clazz, in);
+}
+
+// Clears the data and objects of a parcel so that it can be reused, without
+// releasing its allocation or changing its format.
+static binder_status_t ResetParcel(AParcel* parcel) {
+    status_t status = parcel->get()->setDataSize(0);
+    parcel->get()->setDataPosition(0);
+    return PruneStatusT(status);
+}
+
 binder_status_t AIBinder_prepareTransaction(AIBinder* binder, AParcel** in) {
     if (binder == nullptr || in == nullptr) {
         LOG(ERROR) << __func__ << ": requires non-null parameters.";
@@ -637,13 +659,7 @@ binder_status_t AIBinder_prepareTransaction(AIBinder* binder, AParcel** in) {
     }
 
     *in = new AParcel(binder);
-    (*in)->get()->markForBinder(binder->getBinder());
-
-    status_t status = android::OK;
-    if (clazz->writeHeader) {
-        status = (*in)->get()->writeInterfaceToken(clazz->getInterfaceDescriptor());
-    }
-    binder_status_t ret = PruneStatusT(status);
+    binder_status_t ret = WriteTransactionHeader(binder, clazz, *in);
 
     if (ret != STATUS_OK) {
         delete *in;
@@ -705,6 +721,57 @@ binder_status_t AIBinder_transact(AIBinder* binder, transaction_code_t code, APa
     return ret;
 }
 
+binder_status_t AIBinder_resetTransaction(AIBinder* binder, AParcel* in) {
+    if (binder == nullptr || in == nullptr) {
+        LOG(ERROR) << __func__ << ": requires non-null parameters.";
+        return STATUS_UNEXPECTED_NULL;
+    }
+    if (in->getBinder() != binder) {
+        LOG(ERROR) << __func__ << ": parcel is associated with binder object " << in->getBinder()
+                   << " but called with " << binder;
+        return STATUS_BAD_VALUE;
+    }
+    const AIBinder_Class* clazz = binder->getClass();
+    if (clazz == nullptr) {
+        LOG(ERROR) << __func__ << ": Class must be defined for a remote binder transaction.";
+        return STATUS_INVALID_OPERATION;
+    }
+
+    binder_status_t ret = ResetParcel(in);
+    if (ret != STATUS_OK) return ret;
+    return WriteInterfaceHeader(clazz, in);
+}
+
+binder_status_t AIBinder_transactInto(AIBinder* binder, transaction_code_t code, const AParcel* in,
+                                      AParcel* out, binder_flags_t flags) {
+    if (!isUserCommand(code)) {
+        LOG(ERROR) << __func__ << ": Only user-defined transactions can be made from the NDK.";
+        return STATUS_UNKNOWN_TRANSACTION;
+    }
+
+    constexpr binder_flags_t kAllFlags = FLAG_PRIVATE_VENDOR | FLAG_ONEWAY | FLAG_CLEAR_BUF;
+    if ((flags & ~kAllFlags) != 0) {
+        LOG(ERROR) << __func__ << ": Unrecognized flags sent: " << flags;
+        return STATUS_BAD_VALUE;
+    }
+
+    if (binder == nullptr || in == nullptr || out == nullptr) {
+        LOG(ERROR) << __func__ << ": requires non-null parameters.";
+        return STATUS_UNEXPECTED_NULL;
+    }
+
+    if (in->getBinder() != binder) {
+        LOG(ERROR) << __func__ << ": parcel is associated with binder object " << binder
+                   << " but called with " << in->getBinder();
+        return STATUS_BAD_VALUE;
+    }
+
+    binder_status_t ret = ResetParcel(out);
+    if (ret != STATUS_OK) return ret;
+    status_t status = binder->getBinder()->transact(code, *in->get(), out->get(), flags);
+    return PruneStatusT(status);
+}
+
 AIBinder_DeathRecipient* AIBinder_DeathRecipient_new(
         AIBinder_DeathRecipient_onBinderDied onBinderDied) {
     if (onBinderDied == nullptr) {
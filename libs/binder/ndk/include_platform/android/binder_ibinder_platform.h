@@ -80,4 +80,42 @@ void AIBinder_setMinSchedulerPolicy(AIBinder* binder, int policy, int priority)
  */
 void AIBinder_setInheritRt(AIBinder* binder, bool inheritRt) __INTRODUCED_IN(33);
 
+#if !defined(__ANDROID_APEX__) && !defined(__ANDROID_VNDK__)
+
+/**
+ * Clears a parcel created by AIBinder_prepareTransaction on the same binder,
+ * and writes the transaction header again, so that the parcel can be reused
+ * for another transaction with AIBinder_transactInto.
+ *
+ * \param binder the binder object the parcel was prepared for.
+ * \param in the parcel to reset. It keeps its association with the binder and
+ *     its allocated capacity.
+ *
+ * \return STATUS_OK on success, STATUS_BAD_VALUE if the parcel was prepared
+ * for a different binder.
+ */
+binder_status_t AIBinder_resetTransaction(AIBinder* binder, AParcel* in);
+
+/**
+ * Like AIBinder_transact, but without taking ownership of the input parcel and
+ * writing the reply into a parcel owned by the caller, so that both parcels can
+ * be reused for later transactions.
+ *
+ * \param binder the binder object to transact on.
+ * \param code the implementation-specific code representing which transaction
+ *     should be taken.
+ * \param in the transaction data, which must have been created by
+ *     AIBinder_prepareTransaction on the same binder. It is not modified.
+ * \param out the parcel to write the reply to. Its previous contents are
+ *     cleared, but its allocated capacity is kept where possible.
+ * \param flags possible flags to alter the way in which the transaction is
+ *     conducted, see AIBinder_transact.
+ *
+ * \return the result from the kernel or from the remote process.
+ */
+binder_status_t AIBinder_transactInto(AIBinder* binder, transaction_code_t code, const AParcel* in,
+                                      AParcel* out, binder_flags_t flags);
+
+#endif
+
 __END_DECLS
@@ -31,6 +31,47 @@ __BEGIN_DECLS
  */
 bool AParcel_getAllowFds(const AParcel*);
 
+/**
+ * Sets the capacity of the parcel data buffer, so that writes up to that size
+ * do not need to reallocate it. If the capacity is smaller than the current
+ * data size, the parcel is left unchanged and STATUS_OK is returned.
+ *
+ * \param parcel The parcel to reserve space in.
+ * \param capacity The new capacity of the parcel data buffer, in bytes.
+ *
+ * \return STATUS_OK on success, STATUS_NO_MEMORY if the buffer could not be
+ * allocated, or STATUS_BAD_VALUE if the capacity is too large.
+ */
+binder_status_t AParcel_setDataCapacity(AParcel* parcel, size_t capacity);
+
+/**
+ * Writes raw bytes to the parcel, without a length prefix. The data is padded
+ * with zeros to a multiple of 4 bytes.
+ *
+ * \param parcel The parcel to write to.
+ * \param buffer The bytes to write. This may only be null if len is 0.
+ * \param len The number of bytes to write.
+ *
+ * \return STATUS_OK on success, STATUS_UNEXPECTED_NULL if buffer is null and
+ * len is not 0.
+ */
+binder_status_t AParcel_writeRawBytes(AParcel* parcel, const uint8_t* buffer, size_t len);
+
+/**
+ * Reads raw bytes written by AParcel_writeRawBytes. The data position is
+ * advanced past the padding to a multiple of 4 bytes.
+ *
+ * \param parcel The parcel to read from.
+ * \param buffer The buffer to read into, which must hold at least len bytes.
+ * This may only be null if len is 0.
+ * \param len The number of bytes to read.
+ *
+ * \return STATUS_OK on success, STATUS_UNEXPECTED_NULL if buffer is null and
+ * len is not 0, or STATUS_NOT_ENOUGH_DATA if the parcel does not hold enough
+ * data.
+ */
+binder_status_t AParcel_readRawBytes(const AParcel* parcel, uint8_t* buffer, size_t len);
+
 #endif
 
 /**
@@ -669,6 +669,30 @@ binder_status_t AParcel_appendFrom(const AParcel* from, AParcel* to, int32_t sta
     return PruneStatusT(status);
 }
 
+binder_status_t AParcel_setDataCapacity(AParcel* parcel, size_t capacity) {
+    if (capacity < parcel->get()->dataSize()) {
+        return STATUS_OK;
+    }
+    status_t status = parcel->get()->setDataCapacity(capacity);
+    return PruneStatusT(status);
+}
+
+binder_status_t AParcel_writeRawBytes(AParcel* parcel, const uint8_t* buffer, size_t len) {
+    if (buffer == nullptr && len > 0) {
+        return STATUS_UNEXPECTED_NULL;
+    }
+    status_t status = parcel->get()->write(buffer, len);
+    return PruneStatusT(status);
+}
+
+binder_status_t AParcel_readRawBytes(const AParcel* parcel, uint8_t* buffer, size_t len) {
+    if (buffer == nullptr && len > 0) {
+        return STATUS_UNEXPECTED_NULL;
+    }
+    status_t status = parcel->get()->read(buffer, len);
+    return PruneStatusT(status);
+}
+
 AParcel* AParcel_create() {
     return new AParcel(nullptr);
 }
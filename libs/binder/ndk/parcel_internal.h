@@ -41,7 +41,7 @@ struct AParcel {
         return AParcel(binder, const_cast<::android::Parcel*>(parcel), false);
     }
 
-    const AIBinder* getBinder() { return mBinder; }
+    const AIBinder* getBinder() const { return mBinder; }
 
    private:
     // This object is associated with a calls to a specific AIBinder object. This is used for sanity
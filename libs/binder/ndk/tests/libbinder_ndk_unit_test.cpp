@@ -661,6 +661,39 @@ TEST(NdkBinder, ConvertToPlatformBinder) {
     }
 }
 
+TEST(NdkBinder, TransactIntoReusesParcels) {
+    ndk::SpAIBinder binder = ndk::SharedRefBase::make<MyBinderNdkUnitTest>()->asBinder();
+
+    AParcel* in = nullptr;
+    ASSERT_EQ(STATUS_OK, AIBinder_prepareTransaction(binder.get(), &in));
+    ndk::ScopedAParcel scopedIn(in);
+    const int32_t headerSize = AParcel_getDataSize(in);
+    ndk::ScopedAParcel out(AParcel_create());
+
+    int32_t replySize = -1;
+    for (int32_t value : {4, 17}) {
+        ASSERT_EQ(STATUS_OK, AParcel_writeInt32(in, value));
+        ASSERT_EQ(STATUS_OK, AIBinder_transactInto(binder.get(),
+                                                   aidl::IBinderNdkUnitTest::TRANSACTION_repeatInt,
+                                                   in, out.get(), FLAG_CLEAR_BUF));
+
+        // The reply replaces the previous one instead of being appended to it.
+        if (replySize >= 0) EXPECT_EQ(replySize, AParcel_getDataSize(out.get()));
+        replySize = AParcel_getDataSize(out.get());
+
+        AStatus* status = nullptr;
+        ASSERT_EQ(STATUS_OK, AParcel_readStatusHeader(out.get(), &status));
+        EXPECT_TRUE(ndk::ScopedAStatus(status).isOk());
+        int32_t repeated = 0;
+        ASSERT_EQ(STATUS_OK, AParcel_readInt32(out.get(), &repeated));
+        EXPECT_EQ(value, repeated);
+
+        // Only the transaction header is left after a reset.
+        ASSERT_EQ(STATUS_OK, AIBinder_resetTransaction(binder.get(), in));
+        EXPECT_EQ(headerSize, AParcel_getDataSize(in));
+    }
+}
+
 class MyResultReceiver : public BnResultReceiver {
    public:
     Mutex mMutex;
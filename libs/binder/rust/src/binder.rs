@@ -17,7 +17,7 @@
 //! Trait definitions for binder objects
 
 use crate::error::{status_t, Result, StatusCode};
-use crate::parcel::{Parcel, BorrowedParcel};
+use crate::parcel::{Parcel, BorrowedParcel, ReadLimits};
 use crate::proxy::{DeathRecipient, SpIBinder, WpIBinder};
 use crate::sys;
 
@@ -64,6 +64,46 @@ pub trait Interface: Send + Sync {
     fn dump(&self, _file: &File, _args: &[&CStr]) -> Result<()> {
         Ok(())
     }
+
+    /// Limits applied to all parcel reads while this Binder object handles an
+    /// incoming transaction.
+    ///
+    /// There are no limits by default. Services that accept calls from
+    /// untrusted clients should override this to bound the memory a single
+    /// transaction can make them allocate.
+    ///
+    /// The limits apply to every parcel read on the thread handling the
+    /// transaction until the handler makes an outgoing call, so they don't
+    /// apply to its replies.
+    fn read_limits(&self) -> ReadLimits {
+        ReadLimits::default()
+    }
+
+    /// What to do when a transaction handler of this Binder object returns
+    /// successfully without reading all of the data sent by the client.
+    ///
+    /// Leftover data usually means that the client and server were built from
+    /// different versions of the interface. It is ignored by default.
+    fn unread_data_policy(&self) -> UnreadDataPolicy {
+        UnreadDataPolicy::Ignore
+    }
+}
+
+/// Handling of transactions whose data was not completely read by the
+/// transaction handler, see [`Interface::unread_data_policy`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum UnreadDataPolicy {
+    /// Accept the transaction without any check.
+    Ignore,
+
+    /// Accept the transaction, but log the interface descriptor, transaction
+    /// code and number of unread bytes.
+    Log,
+
+    /// Log the transaction like [`UnreadDataPolicy::Log`], and fail it with
+    /// `BAD_VALUE`. The handler has already run at this point, so any side
+    /// effects it had are not undone.
+    Reject,
 }
 
 /// Implemented by sync interfaces to specify what the associated async interface is.
@@ -164,6 +204,18 @@ pub trait Remotable: Send + Sync {
     /// object.
     fn on_dump(&self, file: &File, args: &[&CStr]) -> Result<()>;
 
+    /// Limits applied to all parcel reads while handling a transaction on this
+    /// object. See [`Interface::read_limits`].
+    fn read_limits(&self) -> ReadLimits {
+        ReadLimits::default()
+    }
+
+    /// Handling of transactions that leave data unread on this object. See
+    /// [`Interface::unread_data_policy`].
+    fn unread_data_policy(&self) -> UnreadDataPolicy {
+        UnreadDataPolicy::Ignore
+    }
+
     /// Retrieve the class of this remote object.
     ///
     /// This method should always return the same InterfaceClass for the same
@@ -243,6 +295,69 @@ pub trait IBinderInternal: IBinder {
         input_callback(parcel.borrowed())?;
         self.submit_transact(code, parcel, flags)
     }
+
+    /// Clear a [`Parcel`] created by `prepare_transact` on the same binder, and
+    /// write the transaction header again, so that it can be reused for
+    /// another call to `submit_transact_into`. The parcel keeps its
+    /// allocated capacity.
+    ///
+    /// Requires Android 14 (API level 34), and fails with `INVALID_OPERATION`
+    /// on older versions.
+    fn reset_transact(&self, data: &mut Parcel) -> Result<()>;
+
+    /// Perform a generic operation with the object, reusing caller-provided
+    /// parcels instead of allocating new ones.
+    ///
+    /// The provided `data` [`Parcel`] must have been created by a call to
+    /// `prepare_transact` on the same binder. Unlike `submit_transact`, it is
+    /// not consumed. The previous contents of `reply` are cleared, and it
+    /// receives the reply on success.
+    ///
+    /// Like `reset_transact`, this requires Android 14 (API level 34).
+    ///
+    /// # Arguments
+    ///
+    /// * `code` - Transaction code for the operation.
+    /// * `data` - [`Parcel`] with input data.
+    /// * `reply` - [`Parcel`] to receive the reply.
+    /// * `flags` - Transaction flags, e.g. marking the transaction as
+    ///   asynchronous ([`FLAG_ONEWAY`](FLAG_ONEWAY)).
+    fn submit_transact_into(
+        &self,
+        code: TransactionCode,
+        data: &Parcel,
+        reply: &mut Parcel,
+        flags: TransactionFlags,
+    ) -> Result<()>;
+
+    /// Perform a generic operation with the object, reusing caller-provided
+    /// parcels. This is a convenience method that internally calls
+    /// `reset_transact` followed by `submit_transact_into`, so it requires
+    /// Android 14 (API level 34).
+    ///
+    /// This avoids allocating parcels for every call on hot transaction
+    /// paths: `data` is typically created once with `prepare_transact`, and
+    /// `reply` with [`Parcel::new`], then both are reused for every call.
+    ///
+    /// # Arguments
+    /// * `code` - Transaction code for the operation
+    /// * `flags` - Transaction flags, e.g. marking the transaction as
+    ///   asynchronous ([`FLAG_ONEWAY`](FLAG_ONEWAY))
+    /// * `data` - [`Parcel`] created by `prepare_transact` on this binder
+    /// * `reply` - [`Parcel`] to receive the reply
+    /// * `input_callback` A callback for building the `Parcel`.
+    fn transact_into<F: FnOnce(BorrowedParcel<'_>) -> Result<()>>(
+        &self,
+        code: TransactionCode,
+        flags: TransactionFlags,
+        data: &mut Parcel,
+        reply: &mut Parcel,
+        input_callback: F,
+    ) -> Result<()> {
+        self.reset_transact(data)?;
+        input_callback(data.borrowed())?;
+        self.submit_transact_into(code, data, reply, flags)
+    }
 }
 
 /// Interface of binder local or remote objects.
@@ -917,6 +1032,14 @@ macro_rules! declare_binder_interface {
                 self.0.dump(file, args)
             }
 
+            fn read_limits(&self) -> $crate::ReadLimits {
+                self.0.read_limits()
+            }
+
+            fn unread_data_policy(&self) -> $crate::UnreadDataPolicy {
+                self.0.unread_data_policy()
+            }
+
             fn get_class() -> $crate::binder_impl::InterfaceClass {
                 static CLASS_INIT: std::sync::Once = std::sync::Once::new();
                 static mut CLASS: Option<$crate::binder_impl::InterfaceClass> = None;
@@ -1080,9 +1203,178 @@ macro_rules! declare_binder_interface {
 
 /// Declare an AIDL enumeration.
 ///
+/// Besides the declared constants, the generated type has a `name()` method
+/// returning the name of a declared value, and implements `Display` and
+/// `FromStr` using those names. Values that were not declared, for example
+/// ones sent by a newer peer, are displayed and parsed as plain numbers.
+///
 /// This is mainly used internally by the AIDL compiler.
+///
+/// ```
+/// # use binder::declare_binder_enum;
+/// declare_binder_enum! {
+///     Fruit : [i8; 2] {
+///         APPLE = 0,
+///         BANANA = 1,
+///     }
+/// }
+///
+/// assert_eq!(Fruit::BANANA.name(), Some("BANANA"));
+/// assert_eq!(Fruit(7).name(), None);
+/// assert_eq!(Fruit::BANANA.to_string(), "BANANA");
+/// assert_eq!(Fruit(7).to_string(), "7");
+/// assert_eq!("APPLE".parse(), Ok(Fruit::APPLE));
+/// assert_eq!("7".parse(), Ok(Fruit(7)));
+/// assert!("CHERRY".parse::<Fruit>().is_err());
+/// ```
 #[macro_export]
 macro_rules! declare_binder_enum {
+    {
+        $( #[$attr:meta] )*
+        $enum:ident : [$backing:ty; $size:expr] {
+            $( $( #[$value_attr:meta] )* $name:ident = $value:expr, )*
+        }
+    } => {
+        $crate::__declare_binder_newtype! {
+            $( #[$attr] )*
+            $enum : [$backing; $size] {
+                $( $( #[$value_attr] )* $name = $value, )*
+            }
+        }
+
+        impl std::fmt::Display for $enum {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                match self.name() {
+                    Some(name) => f.pad(name),
+                    None => std::fmt::Display::fmt(&self.0, f),
+                }
+            }
+        }
+
+        impl std::str::FromStr for $enum {
+            type Err = $crate::StatusCode;
+
+            fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+                Self::from_name_or_value(s)
+            }
+        }
+    };
+}
+
+/// Declare a set of AIDL bit flags.
+///
+/// This is like [`declare_binder_enum!`], but the generated type also supports
+/// combining values with `|` and `&`, and checking for flags with `contains`.
+/// `Display` lists the names of the set flags separated by `" | "`, followed
+/// by any undeclared bits as a number, and `FromStr` parses the same format.
+///
+/// ```
+/// # use binder::declare_binder_flags;
+/// declare_binder_flags! {
+///     Access : [i32; 3] {
+///         NONE = 0,
+///         READ = 1,
+///         WRITE = 2,
+///     }
+/// }
+///
+/// let access = Access::READ | Access::WRITE;
+/// assert!(access.contains(Access::WRITE));
+/// assert_eq!(access & Access::READ, Access::READ);
+/// assert_eq!(access.to_string(), "READ | WRITE");
+/// assert_eq!((Access::READ | Access(8)).to_string(), "READ | 8");
+/// assert_eq!(Access::NONE.to_string(), "NONE");
+/// assert_eq!("WRITE | READ".parse(), Ok(access));
+/// ```
+#[macro_export]
+macro_rules! declare_binder_flags {
+    {
+        $( #[$attr:meta] )*
+        $enum:ident : [$backing:ty; $size:expr] {
+            $( $( #[$value_attr:meta] )* $name:ident = $value:expr, )*
+        }
+    } => {
+        $crate::__declare_binder_newtype! {
+            $( #[$attr] )*
+            $enum : [$backing; $size] {
+                $( $( #[$value_attr] )* $name = $value, )*
+            }
+        }
+
+        impl $enum {
+            /// Returns `true` if all flags set in `other` are also set in `self`.
+            #[inline(always)]
+            pub const fn contains(&self, other: Self) -> bool {
+                self.0 & other.0 == other.0
+            }
+        }
+
+        impl std::ops::BitOr for $enum {
+            type Output = Self;
+
+            fn bitor(self, rhs: Self) -> Self {
+                Self(self.0 | rhs.0)
+            }
+        }
+
+        impl std::ops::BitOrAssign for $enum {
+            fn bitor_assign(&mut self, rhs: Self) {
+                self.0 |= rhs.0;
+            }
+        }
+
+        impl std::ops::BitAnd for $enum {
+            type Output = Self;
+
+            fn bitand(self, rhs: Self) -> Self {
+                Self(self.0 & rhs.0)
+            }
+        }
+
+        impl std::ops::BitAndAssign for $enum {
+            fn bitand_assign(&mut self, rhs: Self) {
+                self.0 &= rhs.0;
+            }
+        }
+
+        impl std::fmt::Display for $enum {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                if let Some(name) = self.name() {
+                    return f.write_str(name);
+                }
+                let mut remaining = self.0;
+                let mut separator = "";
+                for flag in Self::enum_values().iter() {
+                    if flag.0 != 0 && remaining & flag.0 == flag.0 {
+                        write!(f, "{}{}", separator, flag.name().unwrap_or_default())?;
+                        remaining &= !flag.0;
+                        separator = " | ";
+                    }
+                }
+                if remaining != 0 || separator.is_empty() {
+                    write!(f, "{}{}", separator, remaining)?;
+                }
+                Ok(())
+            }
+        }
+
+        impl std::str::FromStr for $enum {
+            type Err = $crate::StatusCode;
+
+            fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+                s.split('|').try_fold(Self::default(), |flags, part| {
+                    Ok(flags | Self::from_name_or_value(part.trim())?)
+                })
+            }
+        }
+    };
+}
+
+/// Declare the newtype shared by `declare_binder_enum!` and
+/// `declare_binder_flags!`, with its constants and parcel support.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __declare_binder_newtype {
     {
         $( #[$attr:meta] )*
         $enum:ident : [$backing:ty; $size:expr] {
@@ -1101,6 +1393,23 @@ macro_rules! declare_binder_enum {
             pub const fn enum_values() -> [Self; $size] {
                 [$(Self::$name),*]
             }
+
+            /// Returns the name of this value, or `None` if it is not one of
+            /// the declared values.
+            #[allow(unreachable_patterns)]
+            pub fn name(&self) -> Option<&'static str> {
+                match *self {
+                    $( Self::$name => Some(stringify!($name)), )*
+                    _ => None,
+                }
+            }
+
+            fn from_name_or_value(s: &str) -> std::result::Result<Self, $crate::StatusCode> {
+                match s {
+                    $( stringify!($name) => Ok(Self::$name), )*
+                    _ => s.parse().map(Self).or(Err($crate::StatusCode::BAD_VALUE)),
+                }
+            }
         }
 
         impl $crate::binder_impl::Serialize for $enum {
@@ -1114,6 +1423,20 @@ macro_rules! declare_binder_enum {
                 let v: Vec<$backing> = slice.iter().map(|x| x.0).collect();
                 <$backing as $crate::binder_impl::SerializeArray>::serialize_array(&v[..], parcel)
             }
+
+            fn serialize_array_iter<I>(iter: I, parcel: &mut $crate::binder_impl::BorrowedParcel<'_>) -> std::result::Result<(), $crate::StatusCode>
+            where
+                I: ExactSizeIterator<Item = Self>,
+            {
+                <$backing as $crate::binder_impl::SerializeArray>::serialize_array_iter(iter.map(|x| x.0), parcel)
+            }
+
+            fn serialize_array_ref_iter<'a, I>(iter: I, parcel: &mut $crate::binder_impl::BorrowedParcel<'_>) -> std::result::Result<(), $crate::StatusCode>
+            where
+                I: ExactSizeIterator<Item = &'a Self>,
+            {
+                <$backing as $crate::binder_impl::SerializeArray>::serialize_array_ref_iter(iter.map(|x| &x.0), parcel)
+            }
         }
 
         impl $crate::binder_impl::Deserialize for $enum {
@@ -1128,6 +1451,158 @@ macro_rules! declare_binder_enum {
                     <$backing as $crate::binder_impl::DeserializeArray>::deserialize_array(parcel)?;
                 Ok(v.map(|v| v.into_iter().map(Self).collect()))
             }
+
+            fn deserialize_array_element(parcel: &$crate::binder_impl::BorrowedParcel<'_>, index: usize) -> std::result::Result<Self, $crate::StatusCode> {
+                <$backing as $crate::binder_impl::DeserializeArray>::deserialize_array_element(parcel, index).map(Self)
+            }
+        }
+
+        $crate::__impl_arbitrary_for_binder_enum!($enum, $backing);
+    };
+}
+
+/// Implement `arbitrary::Arbitrary` for an enum declared with
+/// `declare_binder_enum!`, if the `arbitrary` feature of this crate is
+/// enabled.
+///
+/// Generated values are usually one of the declared values, but can also be
+/// any other value of the backing type, as sent by a newer peer.
+#[cfg(feature = "arbitrary")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __impl_arbitrary_for_binder_enum {
+    ($enum:ident, $backing:ty) => {
+        impl<'a> $crate::binder_impl::arbitrary::Arbitrary<'a> for $enum {
+            fn arbitrary(
+                u: &mut $crate::binder_impl::arbitrary::Unstructured<'a>,
+            ) -> $crate::binder_impl::arbitrary::Result<Self> {
+                let values = Self::enum_values();
+                if values.is_empty() || u.ratio(1, 8)? {
+                    u.arbitrary::<$backing>().map(Self)
+                } else {
+                    u.choose(&values).copied()
+                }
+            }
         }
     };
 }
+
+#[cfg(not(feature = "arbitrary"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __impl_arbitrary_for_binder_enum {
+    ($enum:ident, $backing:ty) => {};
+}
+
+/// Declare an AIDL union.
+///
+/// This generates an enum with one variant per union field, along with a tag
+/// type declared with [`declare_binder_enum!`] that has one value per field.
+/// The union is written to a parcel as its `i32` tag followed by the value of
+/// the active field, like AIDL unions in C++ and Java, and defaults to the
+/// default value of its first field. Reading a tag that is not declared fails
+/// with `BAD_VALUE`.
+///
+/// This is mainly used internally by the AIDL compiler.
+///
+/// ```
+/// # use binder::declare_binder_union;
+/// declare_binder_union! {
+///     Value(ValueTag) : "android.test.Value" {
+///         IntValue(i32) = 0,
+///         StringValue(String) = 1,
+///     }
+/// }
+///
+/// assert_eq!(Value::default().tag(), ValueTag::IntValue);
+/// ```
+#[macro_export]
+macro_rules! declare_binder_union {
+    {
+        $( #[$attr:meta] )*
+        $union:ident($tag:ident) : $descriptor:literal $(, stability = $stability:ident)? {
+            $( #[$first_attr:meta] )*
+            $first:ident($first_ty:ty) = $first_value:expr,
+            $( $( #[$field_attr:meta] )* $field:ident($field_ty:ty) = $value:expr, )*
+        }
+    } => {
+        $( #[$attr] )*
+        #[derive(Debug)]
+        pub enum $union {
+            $( #[$first_attr] )*
+            #[allow(missing_docs)]
+            $first($first_ty),
+            $(
+                $( #[$field_attr] )*
+                #[allow(missing_docs)]
+                $field($field_ty),
+            )*
+        }
+
+        $crate::declare_binder_enum! {
+            #[doc = concat!("Tags of the fields of [`", stringify!($union), "`].")]
+            $tag : [i32; <[&str]>::len(&[stringify!($first) $(, stringify!($field))*])] {
+                #[allow(non_upper_case_globals)]
+                $first = $first_value,
+                $( #[allow(non_upper_case_globals)] $field = $value, )*
+            }
+        }
+
+        impl $union {
+            /// Returns the tag of the active field.
+            pub fn tag(&self) -> $tag {
+                match self {
+                    Self::$first(_) => $tag::$first,
+                    $( Self::$field(_) => $tag::$field, )*
+                }
+            }
+        }
+
+        impl Default for $union {
+            fn default() -> Self {
+                Self::$first(Default::default())
+            }
+        }
+
+        impl $crate::Parcelable for $union {
+            fn write_to_parcel(
+                &self,
+                parcel: &mut $crate::binder_impl::BorrowedParcel<'_>,
+            ) -> std::result::Result<(), $crate::StatusCode> {
+                parcel.write(&self.tag())?;
+                match self {
+                    Self::$first(value) => parcel.write(value),
+                    $( Self::$field(value) => parcel.write(value), )*
+                }
+            }
+
+            fn read_from_parcel(
+                &mut self,
+                parcel: &$crate::binder_impl::BorrowedParcel<'_>,
+            ) -> std::result::Result<(), $crate::StatusCode> {
+                let tag: $tag = parcel.read()?;
+                *self = match tag {
+                    $tag::$first => Self::$first(parcel.read()?),
+                    $( $tag::$field => Self::$field(parcel.read()?), )*
+                    _ => return Err($crate::StatusCode::BAD_VALUE),
+                };
+                Ok(())
+            }
+        }
+
+        impl $crate::binder_impl::ParcelableMetadata for $union {
+            fn get_descriptor() -> &'static str {
+                $descriptor
+            }
+
+            $(
+                fn get_stability(&self) -> $crate::binder_impl::Stability {
+                    $crate::binder_impl::Stability::$stability
+                }
+            )?
+        }
+
+        $crate::impl_serialize_for_parcelable!($union);
+        $crate::impl_deserialize_for_parcelable!($union);
+    };
+}
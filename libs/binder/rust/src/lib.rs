@@ -103,16 +103,23 @@ mod error;
 mod native;
 mod parcel;
 mod state;
+mod weak;
 
 use binder_ndk_sys as sys;
 
-pub use binder::{BinderFeatures, FromIBinder, IBinder, Interface, Strong, Weak};
+pub use binder::{
+    BinderFeatures, FromIBinder, IBinder, Interface, Strong, UnreadDataPolicy, Weak,
+};
 pub use crate::binder_async::{BinderAsyncPool, BoxFuture};
 pub use error::{ExceptionCode, Status, StatusCode};
 pub use native::{
     add_service, force_lazy_services_persist, is_handling_transaction, register_lazy_service,
 };
-pub use parcel::{ParcelFileDescriptor, Parcelable, ParcelableHolder};
+pub use parcel::{
+    read_persistent, write_persistent, DecodedParcelable, ParcelFileDescriptor, ParcelUuid,
+    Parcelable, ParcelableHolder, ParcelableRegistry, PersistError, PersistableBundle,
+    ReadLimits, SharedMemory, SharedMemoryView, SharedMemoryViewMut, String16,
+};
 pub use proxy::{
     get_declared_instances, get_interface, get_service, is_declared, wait_for_interface,
     wait_for_service, DeathRecipient, SpIBinder, WpIBinder,
@@ -134,11 +141,20 @@ pub mod binder_impl {
     pub use crate::error::status_t;
     pub use crate::native::Binder;
     pub use crate::parcel::{
-        BorrowedParcel, Deserialize, DeserializeArray, DeserializeOption, Parcel,
-        ParcelableMetadata, Serialize, SerializeArray, SerializeOption, NON_NULL_PARCELABLE_FLAG,
-        NULL_PARCELABLE_FLAG,
+        assert_round_trip, read_parcelable_in_frame, round_trip, take_last_read_error,
+        with_read_diagnostics, ArrayIter, BorrowedParcel, Checkpoint, DecodedHolder, Deserialize,
+        DeserializeArray, DeserializeOption, DumpWord, FrameDescriptor, FrameName, FrameTypeName,
+        Parcel, ParcelDiff, ParcelDump, ParcelableMetadata, ReadDiagnostics, ReadErrorContext,
+        ReadFrame, Serialize, SerializeArray, SerializeOption, UnknownFields, WordMismatch,
+        NON_NULL_PARCELABLE_FLAG, NULL_PARCELABLE_FLAG,
     };
     pub use crate::proxy::{AssociateClass, Proxy};
+
+    #[cfg(feature = "arbitrary")]
+    pub use crate::parcel::assert_arbitrary_round_trip;
+    #[cfg(feature = "arbitrary")]
+    #[doc(hidden)]
+    pub use arbitrary;
 }
 
 /// Unstable, in-development API that only allowlisted clients are allowed to use.
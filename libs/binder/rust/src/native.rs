@@ -16,9 +16,10 @@
 
 use crate::binder::{
     AsNative, Interface, InterfaceClassMethods, Remotable, Stability, TransactionCode,
+    UnreadDataPolicy,
 };
 use crate::error::{status_result, status_t, Result, StatusCode};
-use crate::parcel::{BorrowedParcel, Serialize};
+use crate::parcel::{limits, BorrowedParcel, Serialize};
 use crate::proxy::SpIBinder;
 use crate::sys;
 
@@ -281,7 +282,12 @@ impl<T: Remotable> InterfaceClassMethods for Binder<T> {
             let data = BorrowedParcel::from_raw(data as *mut sys::AParcel).unwrap();
             let object = sys::AIBinder_getUserData(binder);
             let binder: &T = &*(object as *const T);
-            binder.on_transact(code, &data, &mut reply)
+            limits::with_read_limits(binder.read_limits(), || {
+                binder.on_transact(code, &data, &mut reply)
+            })
+            .and_then(|()| {
+                check_unread_data(T::get_descriptor(), binder.unread_data_policy(), code, &data)
+            })
         };
         match res {
             Ok(()) => 0i32,
@@ -352,6 +358,31 @@ impl<T: Remotable> InterfaceClassMethods for Binder<T> {
     }
 }
 
+/// Apply the [`UnreadDataPolicy`] of the binder with interface `descriptor` to
+/// the data parcel of a transaction it has successfully handled.
+fn check_unread_data(
+    descriptor: &str,
+    policy: UnreadDataPolicy,
+    code: TransactionCode,
+    data: &BorrowedParcel<'_>,
+) -> Result<()> {
+    if policy == UnreadDataPolicy::Ignore {
+        return Ok(());
+    }
+    let unread = data.get_data_size() - data.get_data_position();
+    if unread <= 0 {
+        return Ok(());
+    }
+    // This crate doesn't depend on a logging library, so like its other
+    // diagnostics, this goes to stderr. init redirects it to the kernel log
+    // for services with the `stdio_to_kmsg` option.
+    eprintln!("Transaction {} on {} left {} bytes of data unread", code, descriptor, unread);
+    match policy {
+        UnreadDataPolicy::Reject => Err(StatusCode::BAD_VALUE),
+        _ => Ok(()),
+    }
+}
+
 impl<T: Remotable> Drop for Binder<T> {
     // This causes C++ to decrease the strong ref count of the `AIBinder`
     // object. We specifically do not drop the `rust_object` here. When C++
@@ -526,3 +557,37 @@ pub fn is_handling_transaction() -> bool {
         sys::AIBinder_isHandlingTransaction()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binder::FIRST_CALL_TRANSACTION;
+    use crate::parcel::Parcel;
+
+    fn check(policy: UnreadDataPolicy, parcel: &Parcel) -> Result<()> {
+        check_unread_data(
+            "android.test.UnreadData",
+            policy,
+            FIRST_CALL_TRANSACTION,
+            parcel.borrowed_ref(),
+        )
+    }
+
+    #[test]
+    fn test_check_unread_data() {
+        let mut parcel = Parcel::new();
+        parcel.write(&1i32).unwrap();
+        parcel.write(&2i32).unwrap();
+        parcel.rewind_to_start().unwrap();
+        assert_eq!(parcel.read::<i32>(), Ok(1));
+
+        assert_eq!(check(UnreadDataPolicy::Reject, &parcel), Err(StatusCode::BAD_VALUE));
+        assert_eq!(check(UnreadDataPolicy::Log, &parcel), Ok(()));
+        assert_eq!(check(UnreadDataPolicy::Ignore, &parcel), Ok(()));
+
+        assert_eq!(parcel.read::<i32>(), Ok(2));
+        for policy in [UnreadDataPolicy::Reject, UnreadDataPolicy::Log, UnreadDataPolicy::Ignore] {
+            assert_eq!(check(policy, &parcel), Ok(()));
+        }
+    }
+}
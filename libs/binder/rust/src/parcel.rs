@@ -20,23 +20,51 @@ use crate::binder::AsNative;
 use crate::error::{status_result, Result, StatusCode};
 use crate::proxy::SpIBinder;
 use crate::sys;
+use crate::weak::{self, weak_status_result};
 
+use std::cell::Cell;
 use std::convert::TryInto;
 use std::marker::PhantomData;
 use std::mem::ManuallyDrop;
 use std::ptr::{self, NonNull};
 use std::fmt;
 
+mod diagnostics;
+mod dump;
 mod file_descriptor;
+pub(crate) mod limits;
 mod parcelable;
 mod parcelable_holder;
-
+mod parcelable_registry;
+mod persist;
+mod persistable_bundle;
+mod round_trip;
+mod shared_memory;
+mod string16;
+mod time;
+mod uuid;
+
+pub use self::diagnostics::{
+    read_parcelable_in_frame, take_last_read_error, with_read_diagnostics, FrameDescriptor,
+    FrameName, FrameTypeName, ReadDiagnostics, ReadErrorContext, ReadFrame,
+};
+pub use self::dump::{DumpWord, ParcelDiff, ParcelDump, WordMismatch};
 pub use self::file_descriptor::ParcelFileDescriptor;
+pub use self::limits::ReadLimits;
 pub use self::parcelable::{
     Deserialize, DeserializeArray, DeserializeOption, Serialize, SerializeArray, SerializeOption,
     Parcelable, NON_NULL_PARCELABLE_FLAG, NULL_PARCELABLE_FLAG,
 };
 pub use self::parcelable_holder::{ParcelableHolder, ParcelableMetadata};
+pub use self::parcelable_registry::{DecodedHolder, DecodedParcelable, ParcelableRegistry};
+pub use self::persist::{read_persistent, write_persistent, PersistError};
+pub use self::persistable_bundle::PersistableBundle;
+#[cfg(feature = "arbitrary")]
+pub use self::round_trip::assert_arbitrary_round_trip;
+pub use self::round_trip::{assert_round_trip, round_trip};
+pub use self::shared_memory::{SharedMemory, SharedMemoryView, SharedMemoryViewMut};
+pub use self::string16::String16;
+pub use self::uuid::ParcelUuid;
 
 /// Container for a message (data and object references) that can be sent
 /// through Binder.
@@ -83,6 +111,22 @@ impl Parcel {
         }
     }
 
+    /// Create a new `Parcel` holding raw data previously returned by
+    /// [`Parcel::marshal`].
+    ///
+    /// The data position of the new parcel is at the start of the data.
+    pub fn unmarshal(data: &[u8]) -> Result<Parcel> {
+        let parcel = Parcel::new();
+        let status = unsafe {
+            // Safety: `Parcel` always contains a valid pointer to an `AParcel`,
+            // and `data` is valid for reads of `data.len()` bytes.
+            sys::AParcel_unmarshal(parcel.ptr.as_ptr(), data.as_ptr(), data.len())
+        };
+        status_result(status)?;
+        parcel.rewind_to_start()?;
+        Ok(parcel)
+    }
+
     /// Create an owned reference to a parcel object from a raw pointer.
     ///
     /// # Safety
@@ -222,6 +266,51 @@ impl<'a> BorrowedParcel<'a> {
         }
     }
 
+    /// Set the capacity of the parcel data buffer to at least `capacity`
+    /// bytes, so that writes up to that size do not need to reallocate it.
+    ///
+    /// The capacity is never reduced below the current data size.
+    ///
+    /// Requires Android 14 (API level 34), and fails with `INVALID_OPERATION`
+    /// on older versions.
+    pub fn set_data_capacity(&mut self, capacity: usize) -> Result<()> {
+        let status = unsafe {
+            // Safety: `BorrowedParcel` always contains a valid pointer to an
+            // `AParcel`, and this call is otherwise safe.
+            weak::AParcel_setDataCapacity(self.as_native_mut(), capacity)
+        };
+        weak_status_result(status)
+    }
+
+    /// Reserve space for at least `additional` more bytes of data after the
+    /// current end of the parcel data.
+    ///
+    /// Like [`BorrowedParcel::set_data_capacity`], this requires Android 14.
+    pub fn reserve(&mut self, additional: usize) -> Result<()> {
+        let size: usize = self.get_data_size().try_into().or(Err(StatusCode::BAD_VALUE))?;
+        let capacity = size.checked_add(additional).ok_or(StatusCode::BAD_VALUE)?;
+        self.set_data_capacity(capacity)
+    }
+
+    /// Write raw bytes to the parcel, without a length prefix.
+    ///
+    /// Like all parcel data, the bytes are padded with zeros to a multiple of
+    /// 4 bytes. They can be read back with [`BorrowedParcel::read_bytes_exact`]
+    /// if the reader knows their length.
+    ///
+    /// Requires Android 14 (API level 34), and fails with `INVALID_OPERATION`
+    /// on older versions.
+    pub fn write_bytes(&mut self, bytes: &[u8]) -> Result<()> {
+        let status = unsafe {
+            // Safety: `BorrowedParcel` always contains a valid pointer to an
+            // `AParcel`, and `bytes` is valid for reads of `bytes.len()` bytes.
+            // If the slice is empty, its pointer may be dangling, but is not
+            // dereferenced.
+            weak::AParcel_writeRawBytes(self.as_native_mut(), bytes.as_ptr(), bytes.len())
+        };
+        weak_status_result(status)
+    }
+
     /// Write a type that implements [`Serialize`] to the parcel.
     pub fn write<S: Serialize + ?Sized>(&mut self, parcelable: &S) -> Result<()> {
         parcelable.serialize(self)
@@ -240,6 +329,37 @@ impl<'a> BorrowedParcel<'a> {
         }
     }
 
+    /// Write an array to the parcel directly from an iterator.
+    ///
+    /// This produces the same output as writing a slice of the same elements,
+    /// but avoids collecting a computed sequence into a temporary `Vec` first.
+    /// Returns `BAD_VALUE` if the iterator yields a different number of
+    /// elements than its reported length.
+    pub fn write_array_iter<T, I>(&mut self, iter: I) -> Result<()>
+    where
+        T: SerializeArray,
+        I: IntoIterator<Item = T>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        T::serialize_array_iter(iter.into_iter(), self)
+    }
+
+    /// Write a nullable array to the parcel directly from an iterator.
+    ///
+    /// `None` is written as a null array, in the same way as writing
+    /// `Option::<Vec<T>>::None`.
+    pub fn write_nullable_array_iter<T, I>(&mut self, iter: Option<I>) -> Result<()>
+    where
+        T: SerializeArray,
+        I: IntoIterator<Item = T>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        match iter {
+            Some(iter) => self.write_array_iter(iter),
+            None => self.write(&-1i32),
+        }
+    }
+
     /// Perform a series of writes to the parcel, prepended with the length
     /// (in bytes) of the written data.
     ///
@@ -271,22 +391,44 @@ impl<'a> BorrowedParcel<'a> {
     where
         for<'b> F: FnOnce(&'b mut WritableSubParcel<'b>) -> Result<()>
     {
-        let start = self.get_data_position();
+        self.sized_write_impl(None, f)
+    }
+
+    /// Perform a series of writes to the parcel like
+    /// [`BorrowedParcel::sized_write`], followed by the unknown fields
+    /// captured by [`BorrowedParcel::sized_read_with_unknown_fields`].
+    ///
+    /// This lets a parcelable that was read from a newer peer be written out
+    /// again without losing the fields this version does not know about.
+    pub fn sized_write_with_unknown_fields<F>(
+        &mut self,
+        unknown_fields: &UnknownFields,
+        f: F,
+    ) -> Result<()>
+    where
+        for<'b> F: FnOnce(&'b mut WritableSubParcel<'b>) -> Result<()>
+    {
+        self.sized_write_impl(Some(unknown_fields), f)
+    }
+
+    fn sized_write_impl<F>(&mut self, unknown_fields: Option<&UnknownFields>, f: F) -> Result<()>
+    where
+        for<'b> F: FnOnce(&'b mut WritableSubParcel<'b>) -> Result<()>
+    {
+        let start = self.checkpoint();
         self.write(&0i32)?;
         {
             let mut subparcel = WritableSubParcel(self.reborrow());
             f(&mut subparcel)?;
         }
-        let end = self.get_data_position();
-        unsafe {
-            self.set_data_position(start)?;
-        }
-        assert!(end >= start);
-        self.write(&(end - start))?;
-        unsafe {
-            self.set_data_position(end)?;
+        if let Some(UnknownFields(Some(data))) = unknown_fields {
+            self.append_all_from(data)?;
         }
-        Ok(())
+        let end = self.checkpoint();
+        self.rewind(start)?;
+        assert!(end.position() >= start.position());
+        self.write(&(end.position() - start.position()))?;
+        self.rewind(end)
     }
 
     /// Returns the current position in the parcel data.
@@ -311,15 +453,55 @@ impl<'a> BorrowedParcel<'a> {
     ///
     /// # Safety
     ///
-    /// This method is safe if `pos` is less than the current size of the parcel
-    /// data buffer. Otherwise, we are relying on correct bounds checking in the
-    /// Parcel C++ code on every subsequent read or write to this parcel. If all
-    /// accesses are bounds checked, this call is still safe, but we can't rely
-    /// on that.
+    /// This method is safe if `pos` is at most the current size of the parcel
+    /// data buffer, where the end of the data is a valid position. Otherwise,
+    /// we are relying on correct bounds checking in the Parcel C++ code on
+    /// every subsequent read or write to this parcel. If all accesses are
+    /// bounds checked, this call is still safe, but we can't rely on that.
     pub unsafe fn set_data_position(&self, pos: i32) -> Result<()> {
         status_result(sys::AParcel_setDataPosition(self.as_native(), pos))
     }
 
+    /// Save the current data position, so that it can be restored later with
+    /// [`BorrowedParcel::rewind`].
+    ///
+    /// The checkpoint can't outlive the borrow of the underlying parcel, but
+    /// does not borrow this `BorrowedParcel`, so data can be written to it
+    /// before rewinding.
+    pub fn checkpoint(&self) -> Checkpoint<'a> {
+        Checkpoint {
+            parcel: self.ptr.as_ptr() as usize,
+            position: self.get_data_position(),
+            _lifetime: PhantomData,
+        }
+    }
+
+    /// Move the data position back to a saved [`Checkpoint`].
+    ///
+    /// Returns `BAD_VALUE` if the checkpoint was taken on a different parcel,
+    /// or if it is past the end of the current parcel data. Rewinding to the
+    /// end of the data is allowed.
+    pub fn rewind(&self, checkpoint: Checkpoint<'_>) -> Result<()> {
+        if checkpoint.parcel != self.ptr.as_ptr() as usize
+            || checkpoint.position > self.get_data_size()
+        {
+            return Err(StatusCode::BAD_VALUE);
+        }
+        unsafe {
+            // Safety: We checked above that the position is at most the
+            // current size of the parcel data.
+            self.set_data_position(checkpoint.position)
+        }
+    }
+
+    /// Move the data position back to the start of the parcel.
+    pub fn rewind_to_start(&self) -> Result<()> {
+        unsafe {
+            // Safety: 0 is always a valid position.
+            self.set_data_position(0)
+        }
+    }
+
     /// Append a subset of another parcel.
     ///
     /// This appends `size` bytes of data from `other` starting at offset
@@ -346,6 +528,26 @@ impl<'a> BorrowedParcel<'a> {
         let size = unsafe { sys::AParcel_getDataSize(other.as_native()) };
         self.append_from(other, 0, size)
     }
+
+    /// Copy the raw data of this parcel into a byte vector.
+    ///
+    /// Fails with `INVALID_OPERATION` if the parcel contains any binders or
+    /// file descriptors, since those cannot be represented as plain bytes.
+    ///
+    /// The raw parcel format is optimized for local IPC and may change between
+    /// platform versions, so the data should not be persisted or sent to other
+    /// devices without some form of versioning.
+    pub fn marshal(&self) -> Result<Vec<u8>> {
+        let len = self.get_data_size().try_into().or(Err(StatusCode::BAD_VALUE))?;
+        let mut buffer = vec![0u8; len];
+        let status = unsafe {
+            // Safety: `BorrowedParcel` always contains a valid pointer to an
+            // `AParcel`, and `buffer` is valid for writes of `len` bytes.
+            sys::AParcel_marshal(self.as_native(), buffer.as_mut_ptr(), 0, len)
+        };
+        status_result(status)?;
+        Ok(buffer)
+    }
 }
 
 /// A segment of a writable parcel, used for [`BorrowedParcel::sized_write`].
@@ -358,12 +560,64 @@ impl<'a> WritableSubParcel<'a> {
     }
 }
 
+/// A saved data position in a parcel, created by [`BorrowedParcel::checkpoint`].
+///
+/// A checkpoint can only be used to rewind the parcel it was taken on. Its
+/// lifetime is that of the borrow of the parcel, so the parcel can't be freed
+/// and its address reused by another parcel while the checkpoint exists.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Checkpoint<'a> {
+    // Only used to check that the checkpoint belongs to the parcel it is used
+    // with, never dereferenced.
+    parcel: usize,
+    position: i32,
+    _lifetime: PhantomData<&'a Parcel>,
+}
+
+impl Checkpoint<'_> {
+    /// Returns the saved data position.
+    pub fn position(&self) -> i32 {
+        self.position
+    }
+}
+
 impl Parcel {
     /// Data written to parcelable is zero'd before being deleted or reallocated.
     pub fn mark_sensitive(&mut self) {
         self.borrowed().mark_sensitive()
     }
 
+    /// Set the capacity of the parcel data buffer to at least `capacity`
+    /// bytes, so that writes up to that size do not need to reallocate it.
+    ///
+    /// The capacity is never reduced below the current data size.
+    ///
+    /// Requires Android 14 (API level 34), and fails with `INVALID_OPERATION`
+    /// on older versions.
+    pub fn set_data_capacity(&mut self, capacity: usize) -> Result<()> {
+        self.borrowed().set_data_capacity(capacity)
+    }
+
+    /// Reserve space for at least `additional` more bytes of data after the
+    /// current end of the parcel data.
+    ///
+    /// Like [`Parcel::set_data_capacity`], this requires Android 14.
+    pub fn reserve(&mut self, additional: usize) -> Result<()> {
+        self.borrowed().reserve(additional)
+    }
+
+    /// Write raw bytes to the parcel, without a length prefix.
+    ///
+    /// Like all parcel data, the bytes are padded with zeros to a multiple of
+    /// 4 bytes. They can be read back with [`Parcel::read_bytes_exact`] if the
+    /// reader knows their length.
+    ///
+    /// Requires Android 14 (API level 34), and fails with `INVALID_OPERATION`
+    /// on older versions.
+    pub fn write_bytes(&mut self, bytes: &[u8]) -> Result<()> {
+        self.borrowed().write_bytes(bytes)
+    }
+
     /// Write a type that implements [`Serialize`] to the parcel.
     pub fn write<S: Serialize + ?Sized>(&mut self, parcelable: &S) -> Result<()> {
         self.borrowed().write(parcelable)
@@ -377,6 +631,34 @@ impl Parcel {
         self.borrowed().write_slice_size(slice)
     }
 
+    /// Write an array to the parcel directly from an iterator.
+    ///
+    /// This produces the same output as writing a slice of the same elements,
+    /// but avoids collecting a computed sequence into a temporary `Vec` first.
+    /// Returns `BAD_VALUE` if the iterator yields a different number of
+    /// elements than its reported length.
+    pub fn write_array_iter<T, I>(&mut self, iter: I) -> Result<()>
+    where
+        T: SerializeArray,
+        I: IntoIterator<Item = T>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        self.borrowed().write_array_iter(iter)
+    }
+
+    /// Write a nullable array to the parcel directly from an iterator.
+    ///
+    /// `None` is written as a null array, in the same way as writing
+    /// `Option::<Vec<T>>::None`.
+    pub fn write_nullable_array_iter<T, I>(&mut self, iter: Option<I>) -> Result<()>
+    where
+        T: SerializeArray,
+        I: IntoIterator<Item = T>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        self.borrowed().write_nullable_array_iter(iter)
+    }
+
     /// Perform a series of writes to the parcel, prepended with the length
     /// (in bytes) of the written data.
     ///
@@ -411,6 +693,20 @@ impl Parcel {
         self.borrowed().sized_write(f)
     }
 
+    /// Perform a series of writes to the parcel like [`Parcel::sized_write`],
+    /// followed by the unknown fields captured by
+    /// [`Parcel::sized_read_with_unknown_fields`].
+    pub fn sized_write_with_unknown_fields<F>(
+        &mut self,
+        unknown_fields: &UnknownFields,
+        f: F,
+    ) -> Result<()>
+    where
+        for<'b> F: FnOnce(&'b mut WritableSubParcel<'b>) -> Result<()>
+    {
+        self.borrowed().sized_write_with_unknown_fields(unknown_fields, f)
+    }
+
     /// Returns the current position in the parcel data.
     pub fn get_data_position(&self) -> i32 {
         self.borrowed_ref().get_data_position()
@@ -425,15 +721,38 @@ impl Parcel {
     ///
     /// # Safety
     ///
-    /// This method is safe if `pos` is less than the current size of the parcel
-    /// data buffer. Otherwise, we are relying on correct bounds checking in the
-    /// Parcel C++ code on every subsequent read or write to this parcel. If all
-    /// accesses are bounds checked, this call is still safe, but we can't rely
-    /// on that.
+    /// This method is safe if `pos` is at most the current size of the parcel
+    /// data buffer, where the end of the data is a valid position. Otherwise,
+    /// we are relying on correct bounds checking in the Parcel C++ code on
+    /// every subsequent read or write to this parcel. If all accesses are
+    /// bounds checked, this call is still safe, but we can't rely on that.
     pub unsafe fn set_data_position(&self, pos: i32) -> Result<()> {
         self.borrowed_ref().set_data_position(pos)
     }
 
+    /// Save the current data position, so that it can be restored later with
+    /// [`Parcel::rewind`].
+    ///
+    /// The checkpoint borrows the parcel. To write data before rewinding, take
+    /// the checkpoint on [`Parcel::borrowed`] instead.
+    pub fn checkpoint(&self) -> Checkpoint<'_> {
+        self.borrowed_ref().checkpoint()
+    }
+
+    /// Move the data position back to a saved [`Checkpoint`].
+    ///
+    /// Returns `BAD_VALUE` if the checkpoint was taken on a different parcel,
+    /// or if it is past the end of the current parcel data. Rewinding to the
+    /// end of the data is allowed.
+    pub fn rewind(&self, checkpoint: Checkpoint<'_>) -> Result<()> {
+        self.borrowed_ref().rewind(checkpoint)
+    }
+
+    /// Move the data position back to the start of the parcel.
+    pub fn rewind_to_start(&self) -> Result<()> {
+        self.borrowed_ref().rewind_to_start()
+    }
+
     /// Append a subset of another parcel.
     ///
     /// This appends `size` bytes of data from `other` starting at offset
@@ -446,6 +765,18 @@ impl Parcel {
     pub fn append_all_from(&mut self, other: &impl AsNative<sys::AParcel>) -> Result<()> {
         self.borrowed().append_all_from(other)
     }
+
+    /// Copy the raw data of this parcel into a byte vector.
+    ///
+    /// Fails with `INVALID_OPERATION` if the parcel contains any binders or
+    /// file descriptors, since those cannot be represented as plain bytes.
+    ///
+    /// The raw parcel format is optimized for local IPC and may change between
+    /// platform versions, so the data should not be persisted or sent to other
+    /// devices without some form of versioning.
+    pub fn marshal(&self) -> Result<Vec<u8>> {
+        self.borrowed_ref().marshal()
+    }
 }
 
 // Data deserialization methods
@@ -462,6 +793,74 @@ impl<'a> BorrowedParcel<'a> {
         x.deserialize_from(self)
     }
 
+    /// Read exactly `buffer.len()` raw bytes from the parcel, as written by
+    /// [`BorrowedParcel::write_bytes`].
+    ///
+    /// The data position is advanced past the padding to a multiple of 4
+    /// bytes. Returns `NOT_ENOUGH_DATA` if the parcel does not hold enough
+    /// data.
+    ///
+    /// Requires Android 14 (API level 34), and fails with `INVALID_OPERATION`
+    /// on older versions.
+    pub fn read_bytes_exact(&self, buffer: &mut [u8]) -> Result<()> {
+        let status = unsafe {
+            // Safety: `BorrowedParcel` always contains a valid pointer to an
+            // `AParcel`, and `buffer` is valid for writes of `buffer.len()`
+            // bytes.
+            weak::AParcel_readRawBytes(self.as_native(), buffer.as_mut_ptr(), buffer.len())
+        };
+        weak_status_result(status)
+    }
+
+    /// Attempt to read a type that implements [`Deserialize`] from this parcel
+    /// without consuming it.
+    ///
+    /// The data position is left unchanged, whether or not the read succeeds.
+    pub fn peek<D: Deserialize>(&self) -> Result<D> {
+        let checkpoint = self.checkpoint();
+        let result = self.read();
+        self.rewind(checkpoint)?;
+        result
+    }
+
+    /// Attempt to read a type that implements [`Deserialize`] from this parcel,
+    /// restoring the data position if the read fails.
+    ///
+    /// This allows trying several decodings of the same data, e.g. a newer
+    /// version of a parcelable before an older one.
+    pub fn try_read<D: Deserialize>(&self) -> Result<D> {
+        self.try_read_with(|parcel| parcel.read())
+    }
+
+    /// Attempt to read a type that implements [`Deserialize`] from this parcel,
+    /// failing with `BAD_VALUE` if it exceeds the given limits.
+    ///
+    /// The limits replace any that are already in effect, such as those of the
+    /// binder handling the current transaction, until the read completes.
+    pub fn read_with_limits<D: Deserialize>(&self, limits: ReadLimits) -> Result<D> {
+        limits::with_read_limits(limits, || self.read())
+    }
+
+    /// Run a sequence of reads from this parcel, restoring the data position
+    /// if any of them fails.
+    pub fn try_read_with<T, F>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&BorrowedParcel<'a>) -> Result<T>,
+    {
+        let checkpoint = self.checkpoint();
+        let recovery = diagnostics::recovery_point();
+        let result = f(self);
+        if result.is_err() {
+            // The checkpoint was taken on this parcel, so rewinding to it can
+            // only fail if `f` broke the parcel. The original error is more
+            // useful to the caller either way.
+            let rewound = self.rewind(checkpoint);
+            debug_assert!(rewound.is_ok(), "failed to rewind parcel: {:?}", rewound);
+            diagnostics::discard_recovered(recovery);
+        }
+        result
+    }
+
     /// Safely read a sized parcelable.
     ///
     /// Read the size of a parcelable, compute the end position
@@ -491,6 +890,31 @@ impl<'a> BorrowedParcel<'a> {
     /// ```
     ///
     pub fn sized_read<F>(&self, f: F) -> Result<()>
+    where
+        for<'b> F: FnOnce(ReadableSubParcel<'b>) -> Result<()>
+    {
+        self.sized_read_impl(None, f)
+    }
+
+    /// Safely read a sized parcelable like [`BorrowedParcel::sized_read`],
+    /// capturing any data the closure did not read into `unknown_fields`.
+    ///
+    /// Trailing data is usually made of fields added in a newer version of the
+    /// parcelable. Passing the captured fields to
+    /// [`BorrowedParcel::sized_write_with_unknown_fields`] writes them back
+    /// verbatim. `unknown_fields` is cleared if there is no trailing data.
+    pub fn sized_read_with_unknown_fields<F>(
+        &self,
+        unknown_fields: &mut UnknownFields,
+        f: F,
+    ) -> Result<()>
+    where
+        for<'b> F: FnOnce(ReadableSubParcel<'b>) -> Result<()>
+    {
+        self.sized_read_impl(Some(unknown_fields), f)
+    }
+
+    fn sized_read_impl<F>(&self, unknown_fields: Option<&mut UnknownFields>, f: F) -> Result<()>
     where
         for<'b> F: FnOnce(ReadableSubParcel<'b>) -> Result<()>
     {
@@ -512,8 +936,20 @@ impl<'a> BorrowedParcel<'a> {
                 _lifetime: PhantomData,
             },
             end_position: end,
+            next_field: Cell::new(0),
         };
-        f(subparcel)?;
+        limits::nested(|| f(subparcel))?;
+
+        if let Some(unknown_fields) = unknown_fields {
+            let position = self.get_data_position();
+            *unknown_fields = if position < end {
+                let mut data = Parcel::new();
+                data.append_from(self, position, end - position)?;
+                UnknownFields(Some(data))
+            } else {
+                UnknownFields::default()
+            };
+        }
 
         // Advance the data position to the actual end,
         // in case the closure read less data than was available
@@ -524,6 +960,52 @@ impl<'a> BorrowedParcel<'a> {
         Ok(())
     }
 
+    /// Read the length of an array and return a lazy iterator over its
+    /// elements.
+    ///
+    /// Unlike reading a `Vec<T>`, this does not allocate storage for the whole
+    /// array up front, so large arrays can be scanned or filtered with bounded
+    /// memory. Returns `UNEXPECTED_NULL` if the array is null.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let mut total = 0i64;
+    /// for value in parcel.read_array_iter::<i32>()? {
+    ///     total += i64::from(value?);
+    /// }
+    /// ```
+    pub fn read_array_iter<T: DeserializeArray>(&self) -> Result<ArrayIter<'_, 'a, T>> {
+        self.read_nullable_array_iter()?
+            .ok_or(StatusCode::UNEXPECTED_NULL)
+    }
+
+    /// Read the length of a nullable array and return a lazy iterator over its
+    /// elements, or `None` if the array is null.
+    pub fn read_nullable_array_iter<T: DeserializeArray>(
+        &self,
+    ) -> Result<Option<ArrayIter<'_, 'a, T>>> {
+        // Validate the length the same way as `ReadAndValidateArraySize` in
+        // the NDK, so errors match those of `deserialize_array`.
+        let len: i32 = self.read()?;
+        if len < -1 {
+            return Err(StatusCode::BAD_VALUE);
+        }
+        if len == -1 {
+            return Ok(None);
+        }
+        if len > self.get_data_size() - self.get_data_position() {
+            return Err(StatusCode::NO_MEMORY);
+        }
+        Ok(Some(ArrayIter {
+            parcel: self,
+            index: 0,
+            len: len.try_into().or(Err(StatusCode::BAD_VALUE))?,
+            failed: false,
+            _element: PhantomData,
+        }))
+    }
+
     /// Read a vector size from the parcel and resize the given output vector to
     /// be correctly sized for that amount of data.
     ///
@@ -535,6 +1017,7 @@ impl<'a> BorrowedParcel<'a> {
         if len < 0 {
             return Err(StatusCode::UNEXPECTED_NULL);
         }
+        limits::check_array_len(len)?;
 
         // usize in Rust may be 16-bit, so i32 may not fit
         let len = len.try_into().unwrap();
@@ -554,6 +1037,7 @@ impl<'a> BorrowedParcel<'a> {
         out_vec: &mut Option<Vec<D>>,
     ) -> Result<()> {
         let len: i32 = self.read()?;
+        limits::check_array_len(len)?;
 
         if len < 0 {
             *out_vec = None;
@@ -573,6 +1057,8 @@ impl<'a> BorrowedParcel<'a> {
 pub struct ReadableSubParcel<'a> {
     parcel: BorrowedParcel<'a>,
     end_position: i32,
+    // Index of the next field, for read diagnostics.
+    next_field: Cell<usize>,
 }
 
 impl<'a> ReadableSubParcel<'a> {
@@ -581,7 +1067,12 @@ impl<'a> ReadableSubParcel<'a> {
         // The caller should have checked this,
         // but it can't hurt to double-check
         assert!(self.has_more_data());
-        D::deserialize(&self.parcel)
+        let index = self.next_field.replace(self.next_field.get() + 1);
+        let frame = || ReadFrame::Field {
+            index,
+            position: self.parcel.get_data_position(),
+        };
+        diagnostics::in_frame(&self.parcel, frame, || D::deserialize(&self.parcel))
     }
 
     /// Check if the sub-parcel has more data to read
@@ -590,6 +1081,86 @@ impl<'a> ReadableSubParcel<'a> {
     }
 }
 
+/// Trailing data of a sized parcelable that the reader did not understand,
+/// captured by [`BorrowedParcel::sized_read_with_unknown_fields`].
+///
+/// Parcelables that are passed through to other processes can keep this
+/// alongside their known fields, so that fields added by newer peers are not
+/// lost when the parcelable is written again.
+#[derive(Debug, Clone, Default)]
+pub struct UnknownFields(Option<Parcel>);
+
+impl UnknownFields {
+    /// Returns true if no unknown fields were captured.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_none()
+    }
+
+    /// Returns the number of bytes of unknown field data.
+    pub fn len(&self) -> usize {
+        self.0.as_ref().map_or(0, |data| data.get_data_size() as usize)
+    }
+}
+
+/// A lazy iterator over the elements of an array in a parcel, returned by
+/// [`BorrowedParcel::read_array_iter`].
+///
+/// Each element is decoded from the parcel when the iterator is advanced. The
+/// iterator stops after the first error. If the iterator is dropped before it
+/// is exhausted, the parcel data position is left in the middle of the array,
+/// so the remaining parcel contents can no longer be read.
+pub struct ArrayIter<'p, 'a, T> {
+    parcel: &'p BorrowedParcel<'a>,
+    index: usize,
+    len: usize,
+    failed: bool,
+    _element: PhantomData<fn() -> T>,
+}
+
+impl<'p, 'a, T: DeserializeArray> ArrayIter<'p, 'a, T> {
+    /// Returns the number of elements that have not been read yet.
+    pub fn remaining(&self) -> usize {
+        if self.failed {
+            0
+        } else {
+            self.len - self.index
+        }
+    }
+}
+
+impl<'p, 'a, T: DeserializeArray> Iterator for ArrayIter<'p, 'a, T> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining() == 0 {
+            return None;
+        }
+        // Elements are read one level deeper, like those of arrays read at
+        // once.
+        let element = limits::nested(|| T::deserialize_array_element(self.parcel, self.index));
+        self.index += 1;
+        if element.is_err() {
+            self.failed = true;
+        }
+        Some(element)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.remaining()))
+    }
+}
+
+impl<'p, 'a, T: DeserializeArray> std::iter::FusedIterator for ArrayIter<'p, 'a, T> {}
+
+impl<'p, 'a, T> fmt::Debug for ArrayIter<'p, 'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ArrayIter")
+            .field("index", &self.index)
+            .field("len", &self.len)
+            .finish()
+    }
+}
+
 impl Parcel {
     /// Attempt to read a type that implements [`Deserialize`] from this parcel.
     pub fn read<D: Deserialize>(&self) -> Result<D> {
@@ -603,6 +1174,54 @@ impl Parcel {
         self.borrowed_ref().read_onto(x)
     }
 
+    /// Read exactly `buffer.len()` raw bytes from the parcel, as written by
+    /// [`Parcel::write_bytes`].
+    ///
+    /// The data position is advanced past the padding to a multiple of 4
+    /// bytes. Returns `NOT_ENOUGH_DATA` if the parcel does not hold enough
+    /// data.
+    ///
+    /// Requires Android 14 (API level 34), and fails with `INVALID_OPERATION`
+    /// on older versions.
+    pub fn read_bytes_exact(&self, buffer: &mut [u8]) -> Result<()> {
+        self.borrowed_ref().read_bytes_exact(buffer)
+    }
+
+    /// Attempt to read a type that implements [`Deserialize`] from this parcel
+    /// without consuming it.
+    ///
+    /// The data position is left unchanged, whether or not the read succeeds.
+    pub fn peek<D: Deserialize>(&self) -> Result<D> {
+        self.borrowed_ref().peek()
+    }
+
+    /// Attempt to read a type that implements [`Deserialize`] from this parcel,
+    /// restoring the data position if the read fails.
+    ///
+    /// This allows trying several decodings of the same data, e.g. a newer
+    /// version of a parcelable before an older one.
+    pub fn try_read<D: Deserialize>(&self) -> Result<D> {
+        self.borrowed_ref().try_read()
+    }
+
+    /// Attempt to read a type that implements [`Deserialize`] from this parcel,
+    /// failing with `BAD_VALUE` if it exceeds the given limits.
+    ///
+    /// The limits replace any that are already in effect, such as those of the
+    /// binder handling the current transaction, until the read completes.
+    pub fn read_with_limits<D: Deserialize>(&self, limits: ReadLimits) -> Result<D> {
+        self.borrowed_ref().read_with_limits(limits)
+    }
+
+    /// Run a sequence of reads from this parcel, restoring the data position
+    /// if any of them fails.
+    pub fn try_read_with<T, F>(&self, f: F) -> Result<T>
+    where
+        F: for<'b> FnOnce(&BorrowedParcel<'b>) -> Result<T>,
+    {
+        self.borrowed_ref().try_read_with(|parcel| f(parcel))
+    }
+
     /// Safely read a sized parcelable.
     ///
     /// Read the size of a parcelable, compute the end position
@@ -638,6 +1257,37 @@ impl Parcel {
         self.borrowed_ref().sized_read(f)
     }
 
+    /// Safely read a sized parcelable like [`Parcel::sized_read`], capturing
+    /// any data the closure did not read into `unknown_fields`.
+    pub fn sized_read_with_unknown_fields<F>(
+        &self,
+        unknown_fields: &mut UnknownFields,
+        f: F,
+    ) -> Result<()>
+    where
+        for<'b> F: FnOnce(ReadableSubParcel<'b>) -> Result<()>
+    {
+        self.borrowed_ref().sized_read_with_unknown_fields(unknown_fields, f)
+    }
+
+    /// Read the length of an array and return a lazy iterator over its
+    /// elements.
+    ///
+    /// Unlike reading a `Vec<T>`, this does not allocate storage for the whole
+    /// array up front, so large arrays can be scanned or filtered with bounded
+    /// memory. Returns `UNEXPECTED_NULL` if the array is null.
+    pub fn read_array_iter<T: DeserializeArray>(&self) -> Result<ArrayIter<'_, '_, T>> {
+        self.borrowed_ref().read_array_iter()
+    }
+
+    /// Read the length of a nullable array and return a lazy iterator over its
+    /// elements, or `None` if the array is null.
+    pub fn read_nullable_array_iter<T: DeserializeArray>(
+        &self,
+    ) -> Result<Option<ArrayIter<'_, '_, T>>> {
+        self.borrowed_ref().read_nullable_array_iter()
+    }
+
     /// Read a vector size from the parcel and resize the given output vector to
     /// be correctly sized for that amount of data.
     ///
@@ -714,9 +1364,55 @@ impl Drop for Parcel {
     }
 }
 
+// Debugging methods
+impl<'a> BorrowedParcel<'a> {
+    /// Take a snapshot of the parcel contents for debugging.
+    ///
+    /// The returned [`ParcelDump`] includes the data size, data position,
+    /// the ranges occupied by binder objects and file descriptors, and a hex
+    /// dump of the data. The data position of the parcel is preserved.
+    pub fn dump(&self) -> ParcelDump {
+        ParcelDump::new(self)
+    }
+
+    /// Compare the contents of this parcel with another one, returning `None`
+    /// if they are identical.
+    ///
+    /// This is meant for diagnosing layout mismatches between two parcels,
+    /// e.g. one written by a client and one expected by the server.
+    pub fn diff(&self, other: &BorrowedParcel<'_>) -> Option<ParcelDiff> {
+        self.dump().diff(&other.dump())
+    }
+}
+
+impl Parcel {
+    /// Take a snapshot of the parcel contents for debugging.
+    ///
+    /// The returned [`ParcelDump`] includes the data size, data position,
+    /// the ranges occupied by binder objects and file descriptors, and a hex
+    /// dump of the data. The data position of the parcel is preserved.
+    pub fn dump(&self) -> ParcelDump {
+        self.borrowed_ref().dump()
+    }
+
+    /// Compare the contents of this parcel with another one, returning `None`
+    /// if they are identical.
+    ///
+    /// This is meant for diagnosing layout mismatches between two parcels,
+    /// e.g. one written by a client and one expected by the server.
+    pub fn diff(&self, other: &Parcel) -> Option<ParcelDiff> {
+        self.borrowed_ref().diff(other.borrowed_ref())
+    }
+}
+
+// These only show the size and position, which are cheap to get. Use
+// `dump` to see the contents.
 impl fmt::Debug for Parcel {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Parcel")
+            .field("ptr", &self.ptr)
+            .field("data_size", &self.get_data_size())
+            .field("data_position", &self.get_data_position())
             .finish()
     }
 }
@@ -724,6 +1420,9 @@ impl fmt::Debug for Parcel {
 impl<'a> fmt::Debug for BorrowedParcel<'a> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("BorrowedParcel")
+            .field("ptr", &self.ptr)
+            .field("data_size", &self.get_data_size())
+            .field("data_position", &self.get_data_position())
             .finish()
     }
 }
@@ -963,3 +1662,458 @@ fn test_append_from() {
     assert_eq!(Err(StatusCode::BAD_VALUE), parcel2.append_from(&parcel1, -1, 4));
     assert_eq!(Err(StatusCode::BAD_VALUE), parcel2.append_from(&parcel1, 2, -1));
 }
+
+#[test]
+fn test_write_array_iter() {
+    fn assert_same_words(expected: &Parcel, actual: &Parcel) {
+        assert_eq!(expected.get_data_size(), actual.get_data_size());
+        unsafe {
+            expected.set_data_position(0).unwrap();
+            actual.set_data_position(0).unwrap();
+        }
+        while expected.get_data_position() < expected.get_data_size() {
+            assert_eq!(expected.read::<u32>(), actual.read::<u32>());
+        }
+    }
+
+    let bytes = [1u8, 2, 3, 4, 5, 6];
+    let mut expected = Parcel::new();
+    expected.write(&bytes[..]).unwrap();
+    let mut actual = Parcel::new();
+    actual.write_array_iter(bytes.iter().copied()).unwrap();
+    assert_same_words(&expected, &actual);
+
+    let i8s = [-1i8, 2, -3];
+    let mut expected = Parcel::new();
+    expected.write(&i8s[..]).unwrap();
+    let mut actual = Parcel::new();
+    actual.write_array_iter(i8s.iter().copied()).unwrap();
+    assert_same_words(&expected, &actual);
+
+    let strs = ["a", "bc", "def"];
+    let mut expected = Parcel::new();
+    expected.write(&strs[..]).unwrap();
+    let mut actual = Parcel::new();
+    actual.write_array_iter((0..3).map(|i| strs[i].to_string())).unwrap();
+    assert_same_words(&expected, &actual);
+
+    let mut expected = Parcel::new();
+    expected.write(&(0..10i64).collect::<Vec<_>>()).unwrap();
+    expected.write(&(None as Option<Vec<i32>>)).unwrap();
+    let mut actual = Parcel::new();
+    actual.write_array_iter((0..10i32).map(i64::from)).unwrap();
+    actual.write_nullable_array_iter(None::<Vec<i32>>).unwrap();
+    assert_same_words(&expected, &actual);
+}
+
+#[test]
+fn test_read_array_iter() {
+    let mut parcel = Parcel::new();
+    parcel.write(&[1u8, 2, 3, 4, 5][..]).unwrap();
+    parcel.write(&["a", "bc", "def"][..]).unwrap();
+    parcel.write(&(None as Option<Vec<i32>>)).unwrap();
+    parcel.write(&[10i64, 20, 30][..]).unwrap();
+    parcel.write(&7i32).unwrap();
+
+    unsafe {
+        parcel.set_data_position(0).unwrap();
+    }
+
+    let bytes: Result<Vec<u8>> = parcel.read_array_iter::<u8>().unwrap().collect();
+    assert_eq!(bytes, Ok(vec![1, 2, 3, 4, 5]));
+
+    let mut strings = parcel.read_array_iter::<String>().unwrap();
+    assert_eq!(strings.remaining(), 3);
+    assert_eq!(strings.next(), Some(Ok("a".to_string())));
+    assert_eq!(strings.next(), Some(Ok("bc".to_string())));
+    assert_eq!(strings.next(), Some(Ok("def".to_string())));
+    assert_eq!(strings.next(), None);
+
+    assert!(parcel.read_nullable_array_iter::<i32>().unwrap().is_none());
+
+    let mut longs = parcel.read_array_iter::<i64>().unwrap();
+    assert_eq!(longs.find(|v| *v == Ok(20)), Some(Ok(20)));
+    assert_eq!(longs.next(), Some(Ok(30)));
+    assert_eq!(parcel.read::<i32>(), Ok(7));
+
+    let mut parcel = Parcel::new();
+    parcel.write(&100i32).unwrap();
+    parcel.write(&1i32).unwrap();
+    unsafe {
+        parcel.set_data_position(0).unwrap();
+    }
+    assert_eq!(parcel.read_array_iter::<i32>().err(), Some(StatusCode::NO_MEMORY));
+}
+
+#[test]
+fn test_dump_and_diff() {
+    let mut parcel1 = Parcel::new();
+    parcel1.write(&1i32).unwrap();
+    parcel1.write(&0x01020304u32).unwrap();
+    parcel1.write(&3i32).unwrap();
+
+    let dump = parcel1.dump();
+    assert_eq!(dump.data_size(), 12);
+    assert_eq!(dump.data_position(), 12);
+    assert!(dump.object_ranges().is_empty());
+    assert_eq!(dump.words()[1], DumpWord::Data(0x01020304u32.to_ne_bytes()));
+    assert_eq!(parcel1.get_data_position(), 12);
+
+    let parcel2 = parcel1.clone();
+    assert_eq!(parcel1.diff(&parcel2), None);
+
+    let mut parcel3 = Parcel::new();
+    parcel3.write(&1i32).unwrap();
+    parcel3.write(&2i32).unwrap();
+    let diff = parcel1.diff(&parcel3).expect("Parcels should differ");
+    assert_eq!(diff.data_sizes(), (12, 8));
+    assert_eq!(diff.first_mismatch_offset(), 4);
+    assert_eq!(diff.mismatches().len(), 2);
+    assert_eq!(diff.mismatches()[1].right, None);
+    assert!(diff.to_string().starts_with("parcels differ at offset 0x4"));
+
+    let mut parcel4 = Parcel::new();
+    parcel4.write(&1i32).unwrap();
+    let binder = crate::native::Binder::new(());
+    parcel4.write(&crate::Interface::as_binder(&binder)).unwrap();
+    parcel4.write(&2i32).unwrap();
+    let dump = parcel4.dump();
+    assert_eq!(dump.words()[0], DumpWord::Data(1i32.to_ne_bytes()));
+    assert_eq!(dump.object_ranges().len(), 1);
+    assert_eq!(dump.object_ranges()[0].start, 4);
+    assert_eq!(dump.words().last(), Some(&DumpWord::Data(2i32.to_ne_bytes())));
+}
+
+#[test]
+fn test_debug() {
+    let mut parcel = Parcel::new();
+    parcel.write(&1i32).unwrap();
+    let debug = format!("{:?}", parcel);
+    assert!(debug.starts_with("Parcel { ptr: "), "{}", debug);
+    assert!(debug.ends_with("data_size: 4, data_position: 4 }"), "{}", debug);
+}
+
+#[test]
+fn test_checkpoint() {
+    let mut parcel = Parcel::new();
+    let mut borrowed = parcel.borrowed();
+    // Checkpoints don't borrow the `BorrowedParcel`, so it can be written to.
+    let start = borrowed.checkpoint();
+    borrowed.write(&1i32).unwrap();
+    borrowed.write(&2i32).unwrap();
+    let end = borrowed.checkpoint();
+    assert_eq!(end.position(), borrowed.get_data_size());
+    // Rewinding to the end of the data is allowed.
+    assert_eq!(borrowed.rewind(end), Ok(()));
+    assert_eq!(borrowed.rewind(start), Ok(()));
+
+    assert_eq!(parcel.peek::<i32>(), Ok(1));
+    assert_eq!(parcel.read::<i32>(), Ok(1));
+    let second = parcel.checkpoint();
+    assert_eq!(second.position(), 4);
+    assert_eq!(parcel.read::<i32>(), Ok(2));
+    assert_eq!(parcel.peek::<i32>(), Err(StatusCode::NOT_ENOUGH_DATA));
+    assert_eq!(parcel.get_data_position(), 8);
+
+    assert_eq!(parcel.rewind(second), Ok(()));
+    assert_eq!(parcel.read::<i32>(), Ok(2));
+
+    let other = Parcel::new();
+    assert_eq!(other.rewind(second), Err(StatusCode::BAD_VALUE));
+
+    assert_eq!(parcel.rewind_to_start(), Ok(()));
+    assert_eq!(parcel.read::<i32>(), Ok(1));
+}
+
+#[test]
+fn test_try_read() {
+    let mut parcel = Parcel::new();
+    parcel.write(&1i32).unwrap();
+    parcel.write(&2i32).unwrap();
+    parcel.write(&3i32).unwrap();
+    assert_eq!(parcel.rewind_to_start(), Ok(()));
+
+    // A composite read that runs out of data in the middle.
+    assert_eq!(
+        parcel.try_read_with(|parcel| {
+            let a: i32 = parcel.read()?;
+            let b: i64 = parcel.read()?;
+            let c: i32 = parcel.read()?;
+            Ok((a, b, c))
+        }),
+        Err(StatusCode::NOT_ENOUGH_DATA)
+    );
+    assert_eq!(parcel.get_data_position(), 0);
+
+    assert_eq!(parcel.try_read::<i32>(), Ok(1));
+    assert_eq!(parcel.try_read::<i32>(), Ok(2));
+    assert_eq!(parcel.get_data_position(), 8);
+
+    assert!(parcel.try_read::<String>().is_err());
+    assert_eq!(parcel.get_data_position(), 8);
+    assert_eq!(parcel.try_read::<i32>(), Ok(3));
+}
+
+#[test]
+fn test_read_with_limits() {
+    let mut parcel = Parcel::new();
+    parcel.write(&[1i32, 2, 3][..]).unwrap();
+    parcel.write("hello").unwrap();
+    parcel.write(&vec!["a".to_string(), "b".to_string()]).unwrap();
+
+    let limits = ReadLimits { max_array_len: 2, max_string_bytes: 4, max_depth: 0 };
+
+    assert_eq!(parcel.rewind_to_start(), Ok(()));
+    assert_eq!(
+        parcel.try_read_with(|parcel| parcel.read_with_limits::<Vec<i32>>(limits)),
+        Err(StatusCode::BAD_VALUE)
+    );
+    assert_eq!(parcel.read::<Vec<i32>>(), Ok(vec![1, 2, 3]));
+
+    let checkpoint = parcel.checkpoint();
+    assert_eq!(parcel.read_with_limits::<String>(limits), Err(StatusCode::BAD_VALUE));
+    assert_eq!(parcel.rewind(checkpoint), Ok(()));
+    assert_eq!(
+        parcel.read_with_limits::<String>(ReadLimits { max_string_bytes: 5, ..limits }),
+        Ok("hello".to_string())
+    );
+
+    let checkpoint = parcel.checkpoint();
+    assert_eq!(parcel.read_with_limits::<Vec<String>>(limits), Err(StatusCode::BAD_VALUE));
+    assert_eq!(parcel.rewind(checkpoint), Ok(()));
+    assert_eq!(
+        parcel.read_with_limits::<Vec<String>>(ReadLimits { max_depth: 1, ..limits }),
+        Ok(vec!["a".to_string(), "b".to_string()])
+    );
+
+    let mut out = vec![0i32; 0];
+    assert_eq!(parcel.rewind_to_start(), Ok(()));
+    assert_eq!(
+        limits::with_read_limits(limits, || parcel.resize_out_vec(&mut out)),
+        Err(StatusCode::BAD_VALUE)
+    );
+
+    // Outgoing transactions lift the limits until the end of the scope.
+    assert_eq!(parcel.rewind_to_start(), Ok(()));
+    let read = limits::with_read_limits(limits, || {
+        limits::clear_read_limits();
+        parcel.read::<Vec<i32>>()
+    });
+    assert_eq!(read, Ok(vec![1, 2, 3]));
+}
+
+#[test]
+fn test_read_depth_limit() {
+    let strings = vec![vec!["a".to_string()], vec!["b".to_string(), "c".to_string()]];
+    let ints = vec![vec![1i32], vec![2, 3]];
+    let mut parcel = Parcel::new();
+    parcel.write(&strings).unwrap();
+    parcel.write(&ints).unwrap();
+    assert_eq!(parcel.rewind_to_start(), Ok(()));
+
+    // Each array is one nesting level, whatever the type of its elements.
+    let shallow = ReadLimits { max_depth: 1, ..ReadLimits::UNLIMITED };
+    let deep = ReadLimits { max_depth: 2, ..ReadLimits::UNLIMITED };
+    assert_eq!(
+        parcel.try_read_with(|parcel| parcel.read_with_limits::<Vec<Vec<String>>>(shallow)),
+        Err(StatusCode::BAD_VALUE)
+    );
+    assert_eq!(parcel.read_with_limits::<Vec<Vec<String>>>(deep), Ok(strings));
+    assert_eq!(
+        parcel.try_read_with(|parcel| parcel.read_with_limits::<Vec<Vec<i32>>>(shallow)),
+        Err(StatusCode::BAD_VALUE)
+    );
+    assert_eq!(parcel.read_with_limits::<Vec<Vec<i32>>>(deep), Ok(ints));
+}
+
+#[test]
+fn test_marshal() {
+    let mut parcel = Parcel::new();
+    parcel.write(&42i32).unwrap();
+    parcel.write("Hello, world!").unwrap();
+    parcel.write(&[1u8, 2, 3][..]).unwrap();
+
+    let data = parcel.marshal().unwrap();
+    assert_eq!(data.len(), parcel.get_data_size() as usize);
+
+    let unmarshalled = Parcel::unmarshal(&data).unwrap();
+    assert_eq!(parcel.diff(&unmarshalled), None);
+    assert_eq!(unmarshalled.get_data_position(), 0);
+    assert_eq!(unmarshalled.read::<i32>(), Ok(42));
+    assert_eq!(unmarshalled.read::<String>(), Ok("Hello, world!".to_string()));
+    assert_eq!(unmarshalled.read::<Vec<u8>>(), Ok(vec![1, 2, 3]));
+
+    assert_eq!(Parcel::new().marshal(), Ok(vec![]));
+
+    let binder = crate::native::Binder::new(());
+    parcel.write(&crate::Interface::as_binder(&binder)).unwrap();
+    assert_eq!(parcel.marshal(), Err(StatusCode::INVALID_OPERATION));
+}
+
+#[test]
+fn test_unknown_fields() {
+    // A newer version of a parcelable, with a third field.
+    let mut parcel = Parcel::new();
+    parcel
+        .sized_write(|subparcel| {
+            subparcel.write(&1i32)?;
+            subparcel.write(&2i32)?;
+            subparcel.write("new field")
+        })
+        .unwrap();
+    parcel.write(&42i32).unwrap();
+    let original = parcel.clone();
+
+    // An older reader that only knows about the first two fields.
+    assert_eq!(parcel.rewind_to_start(), Ok(()));
+    let mut unknown_fields = UnknownFields::default();
+    let mut known = (0i32, 0i32);
+    parcel
+        .sized_read_with_unknown_fields(&mut unknown_fields, |subparcel| {
+            known.0 = subparcel.read()?;
+            known.1 = subparcel.read()?;
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(known, (1, 2));
+    assert!(!unknown_fields.is_empty());
+    assert_eq!(parcel.read::<i32>(), Ok(42));
+
+    let mut rewritten = Parcel::new();
+    rewritten
+        .sized_write_with_unknown_fields(&unknown_fields, |subparcel| {
+            subparcel.write(&known.0)?;
+            subparcel.write(&known.1)
+        })
+        .unwrap();
+    rewritten.write(&42i32).unwrap();
+    assert_eq!(original.diff(&rewritten), None);
+
+    // Reading everything leaves no unknown fields.
+    assert_eq!(parcel.rewind_to_start(), Ok(()));
+    parcel
+        .sized_read_with_unknown_fields(&mut unknown_fields, |subparcel| {
+            subparcel.read::<i32>()?;
+            subparcel.read::<i32>()?;
+            subparcel.read::<String>()?;
+            Ok(())
+        })
+        .unwrap();
+    assert!(unknown_fields.is_empty());
+    assert_eq!(unknown_fields.len(), 0);
+}
+
+#[test]
+fn test_read_diagnostics() {
+    let mut parcel = Parcel::new();
+    parcel
+        .sized_write(|subparcel| {
+            subparcel.write(&1i32)?;
+            // A string length without any string data.
+            subparcel.write(&5i32)
+        })
+        .unwrap();
+    assert_eq!(parcel.rewind_to_start(), Ok(()));
+
+    let result = with_read_diagnostics(ReadDiagnostics::Record, || {
+        parcel.sized_read(|subparcel| {
+            subparcel.read::<i32>()?;
+            subparcel.read::<String>()?;
+            Ok(())
+        })
+    });
+
+    let status = result.unwrap_err();
+    let context = take_last_read_error().expect("Read error should have been recorded");
+    assert_eq!(context.status, status);
+    assert_eq!(context.frames, vec![ReadFrame::Field { index: 1, position: 8 }]);
+    assert!(context.to_string().contains("field 1 (0x8)"));
+    assert_eq!(take_last_read_error(), None);
+}
+
+#[test]
+fn test_read_diagnostics_after_recovery() {
+    let mut parcel = Parcel::new();
+    // A string length without any string data.
+    parcel.sized_write(|subparcel| subparcel.write(&5i32)).unwrap();
+    assert_eq!(parcel.rewind_to_start(), Ok(()));
+
+    let borrowed = parcel.borrowed_ref();
+    let result = with_read_diagnostics(ReadDiagnostics::Record, || {
+        read_parcelable_in_frame(borrowed, "android.test.Outer", || {
+            let recovered = borrowed.try_read_with(|parcel| {
+                parcel.sized_read(|subparcel| subparcel.read::<String>().map(|_| ()))
+            });
+            assert!(recovered.is_err());
+            assert_eq!(take_last_read_error(), None);
+            assert_eq!(borrowed.read::<i32>(), Ok(8));
+            assert_eq!(borrowed.read::<i32>(), Ok(5));
+            borrowed.read::<String>().map(|_| ())
+        })
+    });
+
+    let status = result.unwrap_err();
+    let context = take_last_read_error().expect("Read error should have been recorded");
+    assert_eq!(context.status, status);
+    assert_eq!(
+        context.frames,
+        vec![ReadFrame::Parcelable { name: "android.test.Outer", position: 0 }]
+    );
+}
+
+#[test]
+fn test_raw_bytes() {
+    let mut parcel = Parcel::new();
+    assert_eq!(parcel.reserve(64), Ok(()));
+    assert_eq!(parcel.get_data_size(), 0);
+
+    parcel.write_bytes(b"hello").unwrap();
+    assert_eq!(parcel.get_data_size(), 8);
+    parcel.write(&42i32).unwrap();
+    parcel.write_bytes(&[]).unwrap();
+    assert_eq!(parcel.get_data_size(), 12);
+
+    // Never shrinks the data.
+    assert_eq!(parcel.set_data_capacity(0), Ok(()));
+    assert_eq!(parcel.get_data_size(), 12);
+
+    assert_eq!(parcel.rewind_to_start(), Ok(()));
+    let mut buffer = [0u8; 5];
+    parcel.read_bytes_exact(&mut buffer).unwrap();
+    assert_eq!(&buffer, b"hello");
+    assert_eq!(parcel.get_data_position(), 8);
+    assert_eq!(parcel.read::<i32>(), Ok(42));
+    assert_eq!(parcel.read_bytes_exact(&mut buffer), Err(StatusCode::NOT_ENOUGH_DATA));
+}
+
+#[test]
+fn test_union() {
+    crate::declare_binder_union! {
+        TestUnion(TestUnionTag) : "android.test.TestUnion", stability = Vintf {
+            Number(i32) = 0,
+            Names(Vec<String>) = 1,
+        }
+    }
+
+    assert!(matches!(TestUnion::default(), TestUnion::Number(0)));
+    assert_eq!(TestUnionTag::enum_values(), [TestUnionTag::Number, TestUnionTag::Names]);
+    assert_eq!(TestUnion::default().get_stability(), crate::binder::Stability::Vintf);
+
+    let mut parcel = Parcel::new();
+    parcel.write(&TestUnion::Names(vec!["a".to_string()])).unwrap();
+    parcel.write(&TestUnion::Number(7)).unwrap();
+    parcel.write(&None::<TestUnion>).unwrap();
+
+    assert_eq!(parcel.rewind_to_start(), Ok(()));
+    let names: TestUnion = parcel.read().unwrap();
+    assert_eq!(names.tag(), TestUnionTag::Names);
+    assert!(matches!(names, TestUnion::Names(v) if v == ["a"]));
+    assert!(matches!(parcel.read(), Ok(TestUnion::Number(7))));
+    assert!(matches!(parcel.read::<Option<TestUnion>>(), Ok(None)));
+
+    let mut parcel = Parcel::new();
+    parcel.write(&NON_NULL_PARCELABLE_FLAG).unwrap();
+    parcel.write(&2i32).unwrap();
+    assert_eq!(parcel.rewind_to_start(), Ok(()));
+    assert_eq!(parcel.read::<TestUnion>().err(), Some(StatusCode::BAD_VALUE));
+}
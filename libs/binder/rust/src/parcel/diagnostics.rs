@@ -0,0 +1,294 @@
+/*
+ * Copyright (C) 2022 The Android Open Source Project
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Optional tracking of where in a nested parcelable a read failed.
+
+use crate::error::{Result, StatusCode};
+use crate::parcel::{BorrowedParcel, ParcelableMetadata};
+
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::marker::PhantomData;
+
+/// Diagnostic mode for failed parcelable reads, see [`with_read_diagnostics`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ReadDiagnostics {
+    /// No tracking. This is the default, and only costs a check of the mode.
+    Off,
+
+    /// Record the context of the last failed read on each thread, which can
+    /// be retrieved with [`take_last_read_error`].
+    Record,
+
+    /// Record the context like [`ReadDiagnostics::Record`], and also log it.
+    Log,
+}
+
+/// Restores the previous diagnostic mode when dropped, so that it is also
+/// restored if `f` panics.
+struct ModeGuard(ReadDiagnostics);
+
+impl Drop for ModeGuard {
+    fn drop(&mut self) {
+        MODE.with(|mode| mode.set(self.0));
+    }
+}
+
+/// Run `f` with the diagnostic mode for failed parcelable reads set to `mode`
+/// on the current thread.
+///
+/// When enabled, reads of structured parcelables and of their fields through
+/// `ReadableSubParcel::read` keep track of the parcelable type, field index
+/// and data position they are at, so that a failure can be traced back to the
+/// field that caused it. This is meant for debugging compatibility issues
+/// between peers.
+pub fn with_read_diagnostics<T>(mode: ReadDiagnostics, f: impl FnOnce() -> T) -> T {
+    let _guard = ModeGuard(MODE.with(|current| current.replace(mode)));
+    f()
+}
+
+fn mode() -> ReadDiagnostics {
+    MODE.with(Cell::get)
+}
+
+/// A step in the path to a failed read.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ReadFrame {
+    /// Reading a structured parcelable.
+    Parcelable {
+        /// Descriptor of the parcelable, or its Rust type name if it doesn't
+        /// implement [`ParcelableMetadata`].
+        name: &'static str,
+        /// Data position at the start of the parcelable.
+        position: i32,
+    },
+
+    /// Reading a field of the enclosing parcelable.
+    Field {
+        /// Zero-based index of the field within the parcelable.
+        index: usize,
+        /// Data position at the start of the field.
+        position: i32,
+    },
+}
+
+/// The context of a failed parcelable read, recorded when read diagnostics are
+/// enabled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReadErrorContext {
+    /// The error returned by the read.
+    pub status: StatusCode,
+    /// Data position at which the failing read stopped.
+    pub position: i32,
+    /// The nested parcelables and fields being read, outermost first.
+    pub frames: Vec<ReadFrame>,
+}
+
+impl fmt::Display for ReadErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} at data position {:#x}", self.status, self.position)?;
+        for (i, frame) in self.frames.iter().enumerate() {
+            f.write_str(if i == 0 { " while reading " } else { " > " })?;
+            match frame {
+                ReadFrame::Parcelable { name, position } => {
+                    write!(f, "{} ({:#x})", name, position)?
+                }
+                ReadFrame::Field { index, position } => {
+                    write!(f, "field {} ({:#x})", index, position)?
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+thread_local! {
+    static MODE: Cell<ReadDiagnostics> = Cell::new(ReadDiagnostics::Off);
+    static FRAMES: RefCell<Vec<ReadFrame>> = RefCell::new(Vec::new());
+    static LAST_ERROR: RefCell<Option<ReadErrorContext>> = RefCell::new(None);
+    // Set once the innermost frame of a failure has recorded it, so that the
+    // enclosing frames don't overwrite it while the error propagates.
+    static RECORDED: Cell<bool> = Cell::new(false);
+    // Number of failures recorded so far, to tell whether one was recorded
+    // during a read that later recovered.
+    static RECORD_COUNT: Cell<u64> = Cell::new(0);
+}
+
+/// Returns the context of the last failed parcelable read on this thread, if
+/// one was recorded since the last call.
+pub fn take_last_read_error() -> Option<ReadErrorContext> {
+    LAST_ERROR.with(|last| last.borrow_mut().take())
+}
+
+/// Run a read within `parcel`, recording the context if it fails.
+///
+/// `frame` is only called if read diagnostics are enabled.
+pub(crate) fn in_frame<T>(
+    parcel: &BorrowedParcel<'_>,
+    frame: impl FnOnce() -> ReadFrame,
+    f: impl FnOnce() -> Result<T>,
+) -> Result<T> {
+    let mode = mode();
+    if mode == ReadDiagnostics::Off {
+        return f();
+    }
+
+    FRAMES.with(|frames| frames.borrow_mut().push(frame()));
+    let result = f();
+    let is_outermost = FRAMES.with(|frames| {
+        let mut frames = frames.borrow_mut();
+        if let Err(status) = &result {
+            if !RECORDED.with(|recorded| recorded.replace(true)) {
+                let context = ReadErrorContext {
+                    status: *status,
+                    position: parcel.get_data_position(),
+                    frames: frames.clone(),
+                };
+                LAST_ERROR.with(|last| *last.borrow_mut() = Some(context));
+                RECORD_COUNT.with(|count| count.set(count.get() + 1));
+            }
+        }
+        frames.pop();
+        frames.is_empty()
+    });
+
+    if is_outermost {
+        let recorded = RECORDED.with(|recorded| recorded.replace(false));
+        if recorded && result.is_err() && mode == ReadDiagnostics::Log {
+            LAST_ERROR.with(|last| {
+                if let Some(context) = &*last.borrow() {
+                    eprintln!("Failed to read parcelable: {}", context);
+                }
+            });
+        }
+    }
+    result
+}
+
+/// The state of read diagnostics before a read that may recover from a
+/// failure, see [`recovery_point`].
+pub(crate) struct RecoveryPoint(Option<u64>);
+
+/// Mark the start of a read that rewinds the parcel if it fails, such as
+/// `BorrowedParcel::try_read`.
+pub(crate) fn recovery_point() -> RecoveryPoint {
+    if mode() == ReadDiagnostics::Off {
+        return RecoveryPoint(None);
+    }
+    RecoveryPoint(Some(RECORD_COUNT.with(Cell::get)))
+}
+
+/// Forget a failure that was recovered from since `point`, so that it neither
+/// hides nor is mistaken for a later failure.
+pub(crate) fn discard_recovered(point: RecoveryPoint) {
+    if let RecoveryPoint(Some(count)) = point {
+        RECORDED.with(|recorded| recorded.set(false));
+        if RECORD_COUNT.with(Cell::get) != count {
+            LAST_ERROR.with(|last| *last.borrow_mut() = None);
+        }
+    }
+}
+
+/// Read a structured parcelable named `name`, recording it for read
+/// diagnostics.
+///
+/// This is an internal function used by the AIDL-generated `Deserialize`
+/// implementations, which get the name from `__parcelable_frame_name!`.
+#[doc(hidden)]
+pub fn read_parcelable_in_frame(
+    parcel: &BorrowedParcel<'_>,
+    name: &'static str,
+    f: impl FnOnce() -> Result<()>,
+) -> Result<()> {
+    let frame = || ReadFrame::Parcelable { name, position: parcel.get_data_position() };
+    in_frame(parcel, frame, f)
+}
+
+/// Helper for `__parcelable_frame_name!`, which picks between the
+/// [`FrameDescriptor`] and [`FrameTypeName`] implementations of `name` through
+/// auto-ref.
+#[doc(hidden)]
+pub struct FrameName<T: ?Sized>(PhantomData<T>);
+
+impl<T: ?Sized> FrameName<T> {
+    /// Create a new helper for `T`.
+    pub const fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+/// Names a parcelable by its descriptor.
+#[doc(hidden)]
+pub trait FrameDescriptor {
+    /// Returns the name of the parcelable.
+    fn name(&self) -> &'static str;
+}
+
+impl<T: ParcelableMetadata> FrameDescriptor for FrameName<T> {
+    fn name(&self) -> &'static str {
+        T::get_descriptor()
+    }
+}
+
+/// Names a parcelable by its Rust type name, if it has no descriptor.
+#[doc(hidden)]
+pub trait FrameTypeName {
+    /// Returns the name of the parcelable.
+    fn name(&self) -> &'static str;
+}
+
+impl<T: ?Sized> FrameTypeName for &FrameName<T> {
+    fn name(&self) -> &'static str {
+        std::any::type_name::<T>()
+    }
+}
+
+/// Returns the name to record for the parcelable type `$ty` in read
+/// diagnostics: its descriptor if it implements `ParcelableMetadata`, and its
+/// Rust type name otherwise.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __parcelable_frame_name {
+    ($ty:ty) => {{
+        #[allow(unused_imports)]
+        use $crate::binder_impl::{FrameDescriptor as _, FrameTypeName as _};
+        (&$crate::binder_impl::FrameName::<$ty>::new()).name()
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Described;
+
+    impl ParcelableMetadata for Described {
+        fn get_descriptor() -> &'static str {
+            "android.test.Described"
+        }
+    }
+
+    struct Undescribed;
+
+    #[test]
+    fn test_parcelable_frame_name() {
+        assert_eq!(crate::__parcelable_frame_name!(Described), "android.test.Described");
+        assert_eq!(
+            crate::__parcelable_frame_name!(Undescribed),
+            std::any::type_name::<Undescribed>()
+        );
+    }
+}
@@ -0,0 +1,279 @@
+/*
+ * Copyright (C) 2022 The Android Open Source Project
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Human-readable dumps of parcel contents, for debugging.
+
+use crate::parcel::BorrowedParcel;
+
+use std::fmt;
+use std::ops::Range;
+
+/// Maximum number of differing words listed by the `Display` impl of
+/// [`ParcelDiff`].
+const MAX_DISPLAYED_MISMATCHES: usize = 16;
+
+/// A 4-byte word of parcel data in a [`ParcelDump`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DumpWord {
+    /// Plain data, in the byte order it has in the parcel.
+    Data([u8; 4]),
+
+    /// Part of a binder object or file descriptor. Parcels do not allow these
+    /// to be read as plain data, so their contents are not available.
+    Object,
+}
+
+impl fmt::Display for DumpWord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DumpWord::Data(bytes) => {
+                for b in bytes {
+                    write!(f, "{:02x}", b)?;
+                }
+                Ok(())
+            }
+            DumpWord::Object => f.write_str("<object>"),
+        }
+    }
+}
+
+/// A snapshot of the contents of a parcel, for debugging.
+///
+/// The `Display` implementation prints a hex dump of the data, with words that
+/// belong to binder objects or file descriptors shown as `<object>`.
+///
+/// Taking a dump reads through the whole parcel and then restores the original
+/// data position. Reads over object data are rejected by libbinder, which logs
+/// an error for each of them.
+#[derive(Clone, PartialEq, Eq)]
+pub struct ParcelDump {
+    data_size: i32,
+    data_position: i32,
+    words: Vec<DumpWord>,
+}
+
+impl ParcelDump {
+    pub(crate) fn new(parcel: &BorrowedParcel<'_>) -> Self {
+        let data_size = parcel.get_data_size();
+        let checkpoint = parcel.checkpoint();
+        let data_position = checkpoint.position();
+
+        let mut words = Vec::with_capacity((data_size / 4) as usize);
+        let mut offset = 0;
+        while data_size - offset >= 4 {
+            let word = unsafe {
+                // Safety: `offset` is less than the data size of the parcel.
+                parcel.set_data_position(offset)
+            }
+            .and_then(|_| parcel.read::<u32>());
+            words.push(match word {
+                Ok(word) => DumpWord::Data(word.to_ne_bytes()),
+                Err(_) => DumpWord::Object,
+            });
+            offset += 4;
+        }
+
+        // This can only fail for invalid positions, and there is nothing useful
+        // we could do about a failure while dumping anyway.
+        let _ = parcel.rewind(checkpoint);
+
+        Self { data_size, data_position, words }
+    }
+
+    /// Returns the total size of the parcel data.
+    pub fn data_size(&self) -> i32 {
+        self.data_size
+    }
+
+    /// Returns the data position the parcel had when it was dumped.
+    pub fn data_position(&self) -> i32 {
+        self.data_position
+    }
+
+    /// Returns the 4-byte words of parcel data.
+    ///
+    /// If the data size is not a multiple of 4, the trailing bytes are not
+    /// included, since they cannot be read on their own.
+    pub fn words(&self) -> &[DumpWord] {
+        &self.words
+    }
+
+    /// Returns the byte ranges of the parcel that hold binder objects or file
+    /// descriptors.
+    ///
+    /// Adjacent objects are reported as a single range.
+    pub fn object_ranges(&self) -> Vec<Range<i32>> {
+        let mut ranges: Vec<Range<i32>> = Vec::new();
+        for (i, word) in self.words.iter().enumerate() {
+            if *word != DumpWord::Object {
+                continue;
+            }
+            let offset = i as i32 * 4;
+            match ranges.last_mut() {
+                Some(last) if last.end == offset => last.end += 4,
+                _ => ranges.push(offset..offset + 4),
+            }
+        }
+        ranges
+    }
+
+    /// Compare this dump with another one, returning `None` if their contents
+    /// are identical.
+    ///
+    /// Data positions are not compared.
+    pub fn diff(&self, other: &ParcelDump) -> Option<ParcelDiff> {
+        let len = self.words.len().max(other.words.len());
+        let mismatches: Vec<WordMismatch> = (0..len)
+            .filter_map(|i| {
+                let left = self.words.get(i).copied();
+                let right = other.words.get(i).copied();
+                if left == right {
+                    None
+                } else {
+                    Some(WordMismatch { offset: i as i32 * 4, left, right })
+                }
+            })
+            .collect();
+
+        if mismatches.is_empty() && self.data_size == other.data_size {
+            None
+        } else {
+            Some(ParcelDiff {
+                left_size: self.data_size,
+                right_size: other.data_size,
+                mismatches,
+            })
+        }
+    }
+}
+
+impl fmt::Debug for ParcelDump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ParcelDump")
+            .field("data_size", &self.data_size)
+            .field("data_position", &self.data_position)
+            .field("objects", &self.object_ranges())
+            .field("data", &format_args!("[{}]", HexWords(&self.words)))
+            .finish()
+    }
+}
+
+impl fmt::Display for ParcelDump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "data_size: {}, data_position: {}",
+            self.data_size, self.data_position
+        )?;
+        for (line, words) in self.words.chunks(4).enumerate() {
+            write!(f, "{:08x}:", line * 16)?;
+            for word in words {
+                write!(f, " {}", word)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Space-separated hex formatting of a sequence of words.
+struct HexWords<'a>(&'a [DumpWord]);
+
+impl<'a> fmt::Display for HexWords<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, word) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{}", word)?;
+        }
+        Ok(())
+    }
+}
+
+/// A word that differs between two parcels in a [`ParcelDiff`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct WordMismatch {
+    /// Byte offset of the word in both parcels.
+    pub offset: i32,
+    /// The word in the first parcel, or `None` if it is past its end.
+    pub left: Option<DumpWord>,
+    /// The word in the second parcel, or `None` if it is past its end.
+    pub right: Option<DumpWord>,
+}
+
+/// The differences between the contents of two parcels.
+///
+/// The `Display` implementation is meant for logging, and lists the first few
+/// differing words of the two parcels.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParcelDiff {
+    left_size: i32,
+    right_size: i32,
+    mismatches: Vec<WordMismatch>,
+}
+
+impl ParcelDiff {
+    /// Returns the data sizes of the two compared parcels.
+    pub fn data_sizes(&self) -> (i32, i32) {
+        (self.left_size, self.right_size)
+    }
+
+    /// Returns all words that differ between the two parcels.
+    pub fn mismatches(&self) -> &[WordMismatch] {
+        &self.mismatches
+    }
+
+    /// Returns the offset of the first difference between the two parcels.
+    pub fn first_mismatch_offset(&self) -> i32 {
+        self.mismatches
+            .first()
+            .map_or_else(|| self.left_size.min(self.right_size), |m| m.offset)
+    }
+}
+
+impl fmt::Display for ParcelDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "parcels differ at offset {:#x} (data sizes {} and {}, {} differing words)",
+            self.first_mismatch_offset(),
+            self.left_size,
+            self.right_size,
+            self.mismatches.len()
+        )?;
+        for mismatch in self.mismatches.iter().take(MAX_DISPLAYED_MISMATCHES) {
+            write!(f, "\n  {:08x}: ", mismatch.offset)?;
+            match mismatch.left {
+                Some(word) => write!(f, "{}", word)?,
+                None => write!(f, "{:>8}", "-")?,
+            }
+            f.write_str(" != ")?;
+            match mismatch.right {
+                Some(word) => write!(f, "{}", word)?,
+                None => write!(f, "{:>8}", "-")?,
+            }
+        }
+        if self.mismatches.len() > MAX_DISPLAYED_MISMATCHES {
+            write!(
+                f,
+                "\n  ... {} more",
+                self.mismatches.len() - MAX_DISPLAYED_MISMATCHES
+            )?;
+        }
+        Ok(())
+    }
+}
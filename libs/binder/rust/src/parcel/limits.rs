@@ -0,0 +1,152 @@
+/*
+ * Copyright (C) 2022 The Android Open Source Project
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Limits on the resources used when deserializing untrusted parcel data.
+
+use crate::error::{Result, StatusCode};
+
+use std::cell::Cell;
+use std::convert::TryFrom;
+
+/// Limits on the resources that deserializing a parcel may use.
+///
+/// Length prefixes in a parcel come from the sender, so a hostile client can
+/// claim very large arrays or strings to make a service allocate memory before
+/// the read eventually fails. Reads that exceed one of these limits fail with
+/// `BAD_VALUE` before anything is allocated.
+///
+/// Limits apply to all reads on the current thread for the duration of
+/// [`BorrowedParcel::read_with_limits`](crate::binder_impl::BorrowedParcel::read_with_limits),
+/// or while a local binder handles a transaction, using the limits returned by
+/// [`Interface::read_limits`](crate::Interface::read_limits).
+///
+/// Since the limits are tracked per thread rather than per parcel, they are
+/// lifted when a binder handling a transaction makes an outgoing call, so that
+/// they don't apply to its reply. Any reads from the transaction data after
+/// that call are not limited either, so handlers should read all of their
+/// arguments first, as generated AIDL code does.
+///
+/// Nesting depth is counted at parcelable and array boundaries: an array of
+/// strings or integers is one level deep, and an array of such arrays is two
+/// levels deep.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ReadLimits {
+    /// Maximum number of elements in a single array.
+    pub max_array_len: usize,
+
+    /// Maximum length of a single string, in UTF-8 bytes. For a
+    /// [`String16`](crate::String16), which is not decoded, this is the
+    /// maximum number of UTF-16 code units instead.
+    pub max_string_bytes: usize,
+
+    /// Maximum nesting depth of parcelables and arrays. Zero only allows
+    /// values that are neither.
+    pub max_depth: usize,
+}
+
+impl ReadLimits {
+    /// No limits at all. This is the default.
+    pub const UNLIMITED: ReadLimits = ReadLimits {
+        max_array_len: usize::MAX,
+        max_string_bytes: usize::MAX,
+        max_depth: usize::MAX,
+    };
+}
+
+impl Default for ReadLimits {
+    fn default() -> Self {
+        Self::UNLIMITED
+    }
+}
+
+thread_local! {
+    static CURRENT_LIMITS: Cell<ReadLimits> = Cell::new(ReadLimits::UNLIMITED);
+    static CURRENT_DEPTH: Cell<usize> = Cell::new(0);
+}
+
+/// Restores the previous limits when dropped, so that they are also restored
+/// if the reader panics.
+struct LimitsGuard(ReadLimits);
+
+impl Drop for LimitsGuard {
+    fn drop(&mut self) {
+        CURRENT_LIMITS.with(|limits| limits.set(self.0));
+    }
+}
+
+/// Run `f` with `limits` applied to all reads on the current thread.
+pub(crate) fn with_read_limits<T>(limits: ReadLimits, f: impl FnOnce() -> T) -> T {
+    let _guard = LimitsGuard(CURRENT_LIMITS.with(|current| current.replace(limits)));
+    f()
+}
+
+/// Lift the limits and reset the nesting depth on the current thread, until the
+/// end of the enclosing [`with_read_limits`] call. This is done before an
+/// outgoing transaction, so that the limits of a binder handling a
+/// transaction don't apply to the replies of the calls it makes.
+pub(crate) fn clear_read_limits() {
+    CURRENT_LIMITS.with(|limits| limits.set(ReadLimits::UNLIMITED));
+    CURRENT_DEPTH.with(|depth| depth.set(0));
+}
+
+/// Whether the current limits restrict the length of arrays, so that callers
+/// can skip reading the length prefix ahead of time when they don't.
+pub(crate) fn array_len_limited() -> bool {
+    CURRENT_LIMITS.with(|limits| limits.get().max_array_len) != usize::MAX
+}
+
+/// Check the length of an array about to be read against the current limits.
+///
+/// Negative lengths denote null arrays and are always allowed.
+pub(crate) fn check_array_len(len: i32) -> Result<()> {
+    check(len, CURRENT_LIMITS.with(|limits| limits.get().max_array_len))
+}
+
+/// Check the length of a string about to be read against the current limits.
+/// This is in UTF-8 bytes, or in UTF-16 code units for a `String16`.
+pub(crate) fn check_string_len(len: i32) -> Result<()> {
+    check(len, CURRENT_LIMITS.with(|limits| limits.get().max_string_bytes))
+}
+
+fn check(len: i32, max: usize) -> Result<()> {
+    match usize::try_from(len) {
+        Ok(len) if len > max => Err(StatusCode::BAD_VALUE),
+        _ => Ok(()),
+    }
+}
+
+/// Restores the previous nesting depth when dropped. This restores it rather
+/// than decrementing it, since [`clear_read_limits`] may reset it in between.
+struct DepthGuard(usize);
+
+impl Drop for DepthGuard {
+    fn drop(&mut self) {
+        CURRENT_DEPTH.with(|depth| depth.set(self.0));
+    }
+}
+
+/// Run `f` one nesting level deeper, failing with `BAD_VALUE` if that exceeds
+/// the current depth limit.
+pub(crate) fn nested<T>(f: impl FnOnce() -> Result<T>) -> Result<T> {
+    let max_depth = CURRENT_LIMITS.with(|limits| limits.get().max_depth);
+    let depth = CURRENT_DEPTH.with(|depth| depth.get());
+    if depth >= max_depth {
+        return Err(StatusCode::BAD_VALUE);
+    }
+    CURRENT_DEPTH.with(|current| current.set(depth + 1));
+    let _guard = DepthGuard(depth);
+    f()
+}
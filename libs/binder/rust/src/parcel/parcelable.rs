@@ -16,16 +16,20 @@
 
 use crate::binder::{AsNative, FromIBinder, Stability, Strong};
 use crate::error::{status_result, status_t, Result, Status, StatusCode};
+use crate::parcel::limits;
 use crate::parcel::BorrowedParcel;
 use crate::proxy::SpIBinder;
 use crate::sys;
 
+use std::borrow::Cow;
 use std::convert::{TryFrom, TryInto};
 use std::ffi::c_void;
 use std::os::raw::{c_char, c_ulong};
 use std::mem::{self, MaybeUninit, ManuallyDrop};
 use std::ptr;
+use std::rc::Rc;
 use std::slice;
+use std::sync::Arc;
 
 /// Super-trait for Binder parcelables.
 ///
@@ -93,6 +97,95 @@ pub trait SerializeArray: Serialize + Sized {
         };
         status_result(res)
     }
+
+    /// Serialize an array of this type from an iterator into the given parcel.
+    ///
+    /// The output is identical to that of [`SerializeArray::serialize_array`]
+    /// for the same elements, but the elements are written directly from the
+    /// iterator instead of requiring a slice. Returns `BAD_VALUE` if the
+    /// iterator yields a different number of elements than it reported.
+    fn serialize_array_iter<I>(iter: I, parcel: &mut BorrowedParcel<'_>) -> Result<()>
+    where
+        I: ExactSizeIterator<Item = Self>,
+    {
+        let len = write_array_iter_len(&iter, parcel)?;
+        let mut count = 0usize;
+        for element in iter {
+            element.serialize(parcel)?;
+            count += 1;
+        }
+        check_array_iter_len(len, count)
+    }
+
+    /// Serialize an array of this type from an iterator over references into
+    /// the given parcel.
+    ///
+    /// This is what smart pointers like `Arc<T>` use to write arrays with the
+    /// layout of `T`, since they can only lend out their contents. The output
+    /// is identical to that of [`SerializeArray::serialize_array`] for the
+    /// same elements.
+    fn serialize_array_ref_iter<'a, I>(iter: I, parcel: &mut BorrowedParcel<'_>) -> Result<()>
+    where
+        I: ExactSizeIterator<Item = &'a Self>,
+        Self: 'a,
+    {
+        let len = write_array_iter_len(&iter, parcel)?;
+        let mut count = 0usize;
+        for element in iter {
+            element.serialize(parcel)?;
+            count += 1;
+        }
+        check_array_iter_len(len, count)
+    }
+}
+
+/// Write the length prefix of an array that is about to be serialized from an
+/// iterator, and return the expected number of elements.
+fn write_array_iter_len<I: ExactSizeIterator>(
+    iter: &I,
+    parcel: &mut BorrowedParcel<'_>,
+) -> Result<usize> {
+    let len = iter.len();
+    let len_i32: i32 = len.try_into().or(Err(StatusCode::BAD_VALUE))?;
+    parcel.write(&len_i32)?;
+    Ok(len)
+}
+
+/// Check that an iterator yielded the number of elements it promised in its
+/// `ExactSizeIterator::len`, since the length prefix has already been written.
+fn check_array_iter_len(expected: usize, actual: usize) -> Result<()> {
+    if expected == actual {
+        Ok(())
+    } else {
+        Err(StatusCode::BAD_VALUE)
+    }
+}
+
+/// Serialize a byte array from an iterator, using the same packed layout as
+/// `AParcel_writeByteArray`.
+///
+/// The NDK copies the bytes into the parcel and pads the result to a multiple
+/// of 4 bytes with zeroes, so we pack the bytes into native-endian `u32`s to
+/// get an identical layout without buffering the whole array.
+fn serialize_byte_array_iter<I>(iter: I, parcel: &mut BorrowedParcel<'_>) -> Result<()>
+where
+    I: ExactSizeIterator<Item = u8>,
+{
+    let len = write_array_iter_len(&iter, parcel)?;
+    let mut count = 0usize;
+    let mut word = [0u8; 4];
+    for byte in iter {
+        word[count % 4] = byte;
+        count += 1;
+        if count % 4 == 0 {
+            parcel.write(&u32::from_ne_bytes(word))?;
+            word = [0u8; 4];
+        }
+    }
+    if count % 4 != 0 {
+        parcel.write(&u32::from_ne_bytes(word))?;
+    }
+    check_array_iter_len(len, count)
 }
 
 /// Callback to serialize an element of a generic parcelable array.
@@ -128,6 +221,7 @@ unsafe extern "C" fn serialize_element<T: Serialize>(
 pub trait DeserializeArray: Deserialize {
     /// Deserialize an array of type from the given parcel.
     fn deserialize_array(parcel: &BorrowedParcel<'_>) -> Result<Option<Vec<Self>>> {
+        check_array_len_prefix(parcel)?;
         let mut vec: Option<Vec<MaybeUninit<Self>>> = None;
         let res = unsafe {
             // Safety: Safe FFI, vec is the correct opaque type expected by
@@ -151,6 +245,57 @@ pub trait DeserializeArray: Deserialize {
         };
         Ok(vec)
     }
+
+    /// Deserialize a single element of an array of this type.
+    ///
+    /// This is used by [`BorrowedParcel::read_array_iter`] to decode array
+    /// elements one at a time, after the array length has been read. Elements
+    /// are always read in order, and `index` is the zero-based index of the
+    /// element being read. Types with a packed array layout need to override
+    /// this method along with `deserialize_array`.
+    fn deserialize_array_element(parcel: &BorrowedParcel<'_>, _index: usize) -> Result<Self> {
+        Self::deserialize(parcel)
+    }
+}
+
+/// Read the byte at `index` of an array written by `AParcel_writeByteArray`.
+///
+/// The bytes are packed into 4-byte words, so we read a word when reaching its
+/// first byte, and rewind to re-read that same word for each following byte.
+/// This leaves the data position after the last word (including its padding)
+/// once the final element has been read.
+fn deserialize_byte_array_element(parcel: &BorrowedParcel<'_>, index: usize) -> Result<u8> {
+    if index % 4 != 0 {
+        let pos = parcel.get_data_position();
+        unsafe {
+            // Safety: we already read the word containing this byte, which
+            // ends at the current position, so its start is within bounds.
+            parcel.set_data_position(pos - 4)?;
+        }
+    }
+    let word: u32 = parcel.read()?;
+    Ok(word.to_ne_bytes()[index % 4])
+}
+
+/// Check the length prefix of the next array in the parcel against the current
+/// [`ReadLimits`](crate::parcel::ReadLimits), without consuming it.
+///
+/// This must be done before handing the parcel to an NDK array read function,
+/// which allocates the vector as soon as it has read the length.
+pub(crate) fn check_array_len_prefix(parcel: &BorrowedParcel<'_>) -> Result<()> {
+    if !limits::array_len_limited() {
+        return Ok(());
+    }
+    limits::check_array_len(parcel.peek()?)
+}
+
+/// Deserialize an array of `T` one nesting level deeper, so that the array
+/// counts towards the depth limit of the current
+/// [`ReadLimits`](crate::parcel::ReadLimits) once, whatever its elements are.
+fn deserialize_nested_array<T: DeserializeArray>(
+    parcel: &BorrowedParcel<'_>,
+) -> Result<Option<Vec<T>>> {
+    limits::nested(|| DeserializeArray::deserialize_array(parcel))
 }
 
 /// Callback to deserialize a parcelable element.
@@ -259,6 +404,40 @@ unsafe extern "C" fn allocate_vec_with_buffer<T>(
     res
 }
 
+/// Opaque data for [`allocate_string_with_buffer`].
+struct StringBuffer {
+    vec: Option<Vec<u8>>,
+    over_limit: bool,
+}
+
+/// Callback to allocate a buffer for `AParcel_readString`, checking its length
+/// against the current [`ReadLimits`](crate::parcel::ReadLimits) first.
+///
+/// # Safety
+///
+/// The opaque data pointer passed to `AParcel_readString` must be a mutable
+/// pointer to a `StringBuffer`. `buffer` will be assigned a mutable pointer to
+/// the allocated string data if this function returns true.
+unsafe extern "C" fn allocate_string_with_buffer(
+    data: *mut c_void,
+    len: i32,
+    buffer: *mut *mut c_char,
+) -> bool {
+    let string = &mut *(data as *mut StringBuffer);
+    // The length includes the null terminator.
+    if limits::check_string_len(len.saturating_sub(1)).is_err() {
+        string.over_limit = true;
+        return false;
+    }
+    // `Option<Vec<u8>>` is equivalent to the expected `Option<Vec<c_char>>`
+    // for `allocate_vec`.
+    allocate_vec_with_buffer::<u8>(
+        &mut string.vec as *mut _ as *mut c_void,
+        len,
+        buffer as *mut *mut u8,
+    )
+}
+
 /// Callback to allocate a vector for parcel array read functions.
 ///
 /// # Safety
@@ -367,6 +546,36 @@ macro_rules! impl_parcelable {
     {DeserializeArray, $ty:ty, $read_array_fn:path} => {
         impl DeserializeArray for $ty {
             fn deserialize_array(parcel: &BorrowedParcel<'_>) -> Result<Option<Vec<Self>>> {
+                check_array_len_prefix(parcel)?;
+                let mut vec: Option<Vec<MaybeUninit<Self>>> = None;
+                let status = unsafe {
+                    // Safety: `Parcel` always contains a valid pointer to an
+                    // `AParcel`. `allocate_vec<T>` expects the opaque pointer to
+                    // be of type `*mut Option<Vec<MaybeUninit<T>>>`, so `&mut vec` is
+                    // correct for it.
+                    $read_array_fn(
+                        parcel.as_native(),
+                        &mut vec as *mut _ as *mut c_void,
+                        Some(allocate_vec_with_buffer),
+                    )
+                };
+                status_result(status)?;
+                let vec: Option<Vec<Self>> = unsafe {
+                    // Safety: We are assuming that the NDK correctly
+                    // initialized every element of the vector by now, so we
+                    // know that all the MaybeUninits are now properly
+                    // initialized.
+                    vec.map(|vec| vec_assume_init(vec))
+                };
+                Ok(vec)
+            }
+        }
+    };
+
+    {DeserializeByteArray, $ty:ty, $read_array_fn:path} => {
+        impl DeserializeArray for $ty {
+            fn deserialize_array(parcel: &BorrowedParcel<'_>) -> Result<Option<Vec<Self>>> {
+                check_array_len_prefix(parcel)?;
                 let mut vec: Option<Vec<MaybeUninit<Self>>> = None;
                 let status = unsafe {
                     // Safety: `Parcel` always contains a valid pointer to an
@@ -389,6 +598,10 @@ macro_rules! impl_parcelable {
                 };
                 Ok(vec)
             }
+
+            fn deserialize_array_element(parcel: &BorrowedParcel<'_>, index: usize) -> Result<Self> {
+                deserialize_byte_array_element(parcel, index).map(|b| b as $ty)
+            }
         }
     };
 }
@@ -402,12 +615,11 @@ parcelable_primitives! {
 
     // This is only safe because `Option<Vec<u8>>` is interchangeable with
     // `Option<Vec<i8>>` (what the allocator function actually allocates.
-    impl DeserializeArray for u8 = sys::AParcel_readByteArray;
+    impl DeserializeByteArray for u8 = sys::AParcel_readByteArray;
 
     impl Serialize for i8 = sys::AParcel_writeByte;
     impl Deserialize for i8 = sys::AParcel_readByte;
-    impl SerializeArray for i8 = sys::AParcel_writeByteArray;
-    impl DeserializeArray for i8 = sys::AParcel_readByteArray;
+    impl DeserializeByteArray for i8 = sys::AParcel_readByteArray;
 
     impl Serialize for u16 = sys::AParcel_writeChar;
     impl Deserialize for u16 = sys::AParcel_readChar;
@@ -481,6 +693,53 @@ impl SerializeArray for u8 {
         };
         status_result(status)
     }
+
+    fn serialize_array_iter<I>(iter: I, parcel: &mut BorrowedParcel<'_>) -> Result<()>
+    where
+        I: ExactSizeIterator<Item = Self>,
+    {
+        serialize_byte_array_iter(iter, parcel)
+    }
+
+    fn serialize_array_ref_iter<'a, I>(iter: I, parcel: &mut BorrowedParcel<'_>) -> Result<()>
+    where
+        I: ExactSizeIterator<Item = &'a Self>,
+    {
+        serialize_byte_array_iter(iter.copied(), parcel)
+    }
+}
+
+impl SerializeArray for i8 {
+    fn serialize_array(slice: &[Self], parcel: &mut BorrowedParcel<'_>) -> Result<()> {
+        let status = unsafe {
+            // Safety: `Parcel` always contains a valid pointer to an
+            // `AParcel`. If the slice is > 0 length, `slice.as_ptr()` will be a
+            // valid pointer to an array of elements of type `$ty`. If the slice
+            // length is 0, `slice.as_ptr()` may be dangling, but this is safe
+            // since the pointer is not dereferenced if the length parameter is
+            // 0.
+            sys::AParcel_writeByteArray(
+                parcel.as_native_mut(),
+                slice.as_ptr(),
+                slice.len().try_into().or(Err(StatusCode::BAD_VALUE))?,
+            )
+        };
+        status_result(status)
+    }
+
+    fn serialize_array_iter<I>(iter: I, parcel: &mut BorrowedParcel<'_>) -> Result<()>
+    where
+        I: ExactSizeIterator<Item = Self>,
+    {
+        serialize_byte_array_iter(iter.map(|b| b as u8), parcel)
+    }
+
+    fn serialize_array_ref_iter<'a, I>(iter: I, parcel: &mut BorrowedParcel<'_>) -> Result<()>
+    where
+        I: ExactSizeIterator<Item = &'a Self>,
+    {
+        serialize_byte_array_iter(iter.map(|&b| b as u8), parcel)
+    }
 }
 
 impl Serialize for i16 {
@@ -574,21 +833,23 @@ impl SerializeOption for String {
 
 impl Deserialize for Option<String> {
     fn deserialize(parcel: &BorrowedParcel<'_>) -> Result<Self> {
-        let mut vec: Option<Vec<u8>> = None;
+        let mut string = StringBuffer { vec: None, over_limit: false };
         let status = unsafe {
-            // Safety: `Parcel` always contains a valid pointer to an `AParcel`.
-            // `Option<Vec<u8>>` is equivalent to the expected `Option<Vec<i8>>`
-            // for `allocate_vec`, so `vec` is safe to pass as the opaque data
-            // pointer on platforms where char is signed.
+            // Safety: `Parcel` always contains a valid pointer to an `AParcel`,
+            // and `allocate_string_with_buffer` expects the opaque data pointer
+            // to be a `*mut StringBuffer`.
             sys::AParcel_readString(
                 parcel.as_native(),
-                &mut vec as *mut _ as *mut c_void,
-                Some(allocate_vec_with_buffer),
+                &mut string as *mut _ as *mut c_void,
+                Some(allocate_string_with_buffer),
             )
         };
 
+        if string.over_limit {
+            return Err(StatusCode::BAD_VALUE);
+        }
         status_result(status)?;
-        vec.map(|mut s| {
+        string.vec.map(|mut s| {
             // The vector includes a null-terminator and we don't want the
             // string to be null-terminated for Rust.
             s.pop();
@@ -640,7 +901,7 @@ impl<T: SerializeArray> SerializeOption for Vec<T> {
 
 impl<T: DeserializeArray> Deserialize for Vec<T> {
     fn deserialize(parcel: &BorrowedParcel<'_>) -> Result<Self> {
-        DeserializeArray::deserialize_array(parcel)
+        deserialize_nested_array(parcel)
             .transpose()
             .unwrap_or(Err(StatusCode::UNEXPECTED_NULL))
     }
@@ -648,7 +909,7 @@ impl<T: DeserializeArray> Deserialize for Vec<T> {
 
 impl<T: DeserializeArray> DeserializeOption for Vec<T> {
     fn deserialize_option(parcel: &BorrowedParcel<'_>) -> Result<Option<Self>> {
-        DeserializeArray::deserialize_array(parcel)
+        deserialize_nested_array(parcel)
     }
 }
 
@@ -669,7 +930,7 @@ impl<T: SerializeArray, const N: usize> SerializeArray for [T; N] {}
 
 impl<T: DeserializeArray, const N: usize> Deserialize for [T; N] {
     fn deserialize(parcel: &BorrowedParcel<'_>) -> Result<Self> {
-        let vec = DeserializeArray::deserialize_array(parcel)
+        let vec = deserialize_nested_array(parcel)
             .transpose()
             .unwrap_or(Err(StatusCode::UNEXPECTED_NULL))?;
         vec.try_into().or(Err(StatusCode::BAD_VALUE))
@@ -678,7 +939,7 @@ impl<T: DeserializeArray, const N: usize> Deserialize for [T; N] {
 
 impl<T: DeserializeArray, const N: usize> DeserializeOption for [T; N] {
     fn deserialize_option(parcel: &BorrowedParcel<'_>) -> Result<Option<Self>> {
-        let vec = DeserializeArray::deserialize_array(parcel)?;
+        let vec = deserialize_nested_array(parcel)?;
         vec.map(|v| v.try_into().or(Err(StatusCode::BAD_VALUE))).transpose()
     }
 }
@@ -856,7 +1117,10 @@ macro_rules! impl_deserialize_for_parcelable {
                     Err($crate::StatusCode::UNEXPECTED_NULL)
                 } else {
                     use $crate::Parcelable;
-                    self.read_from_parcel(parcel)
+                    let name = $crate::__parcelable_frame_name!(Self);
+                    $crate::binder_impl::read_parcelable_in_frame(parcel, name, || {
+                        self.read_from_parcel(parcel)
+                    })
                 }
             }
         }
@@ -881,8 +1145,11 @@ macro_rules! impl_deserialize_for_parcelable {
                     Ok(())
                 } else {
                     use $crate::Parcelable;
-                    this.get_or_insert_with(Self::default)
-                        .read_from_parcel(parcel)
+                    let parcelable = this.get_or_insert_with(Self::default);
+                    let name = $crate::__parcelable_frame_name!(Self);
+                    $crate::binder_impl::read_parcelable_in_frame(parcel, name, || {
+                        parcelable.read_from_parcel(parcel)
+                    })
                 }
             }
         }
@@ -913,6 +1180,157 @@ impl<T: DeserializeOption> DeserializeOption for Box<T> {
     }
 }
 
+/// Implement the parcelable traits for a shared pointer type by forwarding to
+/// the pointee, so that shared values can be written without cloning them.
+/// Arrays of pointers use the array layout of the pointee.
+macro_rules! impl_parcelable_for_shared_pointer {
+    ($ptr:ident) => {
+        impl<T: Serialize + ?Sized> Serialize for $ptr<T> {
+            fn serialize(&self, parcel: &mut BorrowedParcel<'_>) -> Result<()> {
+                Serialize::serialize(&**self, parcel)
+            }
+        }
+
+        impl<T: SerializeOption + ?Sized> SerializeOption for $ptr<T> {
+            fn serialize_option(
+                this: Option<&Self>,
+                parcel: &mut BorrowedParcel<'_>,
+            ) -> Result<()> {
+                SerializeOption::serialize_option(this.map(|inner| &**inner), parcel)
+            }
+        }
+
+        impl<T: SerializeArray> SerializeArray for $ptr<T> {
+            fn serialize_array(slice: &[Self], parcel: &mut BorrowedParcel<'_>) -> Result<()> {
+                T::serialize_array_ref_iter(slice.iter().map(|inner| &**inner), parcel)
+            }
+
+            fn serialize_array_iter<I>(iter: I, parcel: &mut BorrowedParcel<'_>) -> Result<()>
+            where
+                I: ExactSizeIterator<Item = Self>,
+            {
+                // The pointers have to be kept alive while the pointees are
+                // borrowed, so collect them first.
+                let len = iter.len();
+                let pointers: Vec<Self> = iter.collect();
+                if pointers.len() != len {
+                    return Err(StatusCode::BAD_VALUE);
+                }
+                Self::serialize_array(&pointers, parcel)
+            }
+
+            fn serialize_array_ref_iter<'a, I>(
+                iter: I,
+                parcel: &mut BorrowedParcel<'_>,
+            ) -> Result<()>
+            where
+                I: ExactSizeIterator<Item = &'a Self>,
+                Self: 'a,
+            {
+                T::serialize_array_ref_iter(iter.map(|inner| &**inner), parcel)
+            }
+        }
+
+        impl<T: Deserialize> Deserialize for $ptr<T> {
+            fn deserialize(parcel: &BorrowedParcel<'_>) -> Result<Self> {
+                Deserialize::deserialize(parcel).map($ptr::new)
+            }
+        }
+
+        impl<T: DeserializeOption> DeserializeOption for $ptr<T> {
+            fn deserialize_option(parcel: &BorrowedParcel<'_>) -> Result<Option<Self>> {
+                DeserializeOption::deserialize_option(parcel).map(|t| t.map($ptr::new))
+            }
+        }
+
+        impl<T: DeserializeArray> DeserializeArray for $ptr<T> {
+            fn deserialize_array(parcel: &BorrowedParcel<'_>) -> Result<Option<Vec<Self>>> {
+                let vec = T::deserialize_array(parcel)?;
+                Ok(vec.map(|vec| vec.into_iter().map($ptr::new).collect()))
+            }
+
+            fn deserialize_array_element(
+                parcel: &BorrowedParcel<'_>,
+                index: usize,
+            ) -> Result<Self> {
+                T::deserialize_array_element(parcel, index).map($ptr::new)
+            }
+        }
+    };
+}
+
+impl_parcelable_for_shared_pointer!(Arc);
+impl_parcelable_for_shared_pointer!(Rc);
+
+impl<T: Serialize + ToOwned + ?Sized> Serialize for Cow<'_, T> {
+    fn serialize(&self, parcel: &mut BorrowedParcel<'_>) -> Result<()> {
+        Serialize::serialize(&**self, parcel)
+    }
+}
+
+impl<T: SerializeOption + ToOwned + ?Sized> SerializeOption for Cow<'_, T> {
+    fn serialize_option(this: Option<&Self>, parcel: &mut BorrowedParcel<'_>) -> Result<()> {
+        SerializeOption::serialize_option(this.map(|inner| &**inner), parcel)
+    }
+}
+
+// Arrays are only supported for sized contents, which covers everything but
+// `Cow<str>` and `Cow<[T]>`.
+impl<T: SerializeArray + Clone> SerializeArray for Cow<'_, T> {
+    fn serialize_array(slice: &[Self], parcel: &mut BorrowedParcel<'_>) -> Result<()> {
+        T::serialize_array_ref_iter(slice.iter().map(|inner| &**inner), parcel)
+    }
+
+    fn serialize_array_iter<I>(iter: I, parcel: &mut BorrowedParcel<'_>) -> Result<()>
+    where
+        I: ExactSizeIterator<Item = Self>,
+    {
+        let len = iter.len();
+        let values: Vec<Self> = iter.collect();
+        if values.len() != len {
+            return Err(StatusCode::BAD_VALUE);
+        }
+        Self::serialize_array(&values, parcel)
+    }
+
+    fn serialize_array_ref_iter<'a, I>(iter: I, parcel: &mut BorrowedParcel<'_>) -> Result<()>
+    where
+        I: ExactSizeIterator<Item = &'a Self>,
+        Self: 'a,
+    {
+        T::serialize_array_ref_iter(iter.map(|inner| &**inner), parcel)
+    }
+}
+
+impl<T: ToOwned + ?Sized> Deserialize for Cow<'_, T>
+where
+    T::Owned: Deserialize,
+{
+    fn deserialize(parcel: &BorrowedParcel<'_>) -> Result<Self> {
+        Deserialize::deserialize(parcel).map(Cow::Owned)
+    }
+}
+
+impl<T: ToOwned + ?Sized> DeserializeOption for Cow<'_, T>
+where
+    T::Owned: DeserializeOption,
+{
+    fn deserialize_option(parcel: &BorrowedParcel<'_>) -> Result<Option<Self>> {
+        DeserializeOption::deserialize_option(parcel).map(|t| t.map(Cow::Owned))
+    }
+}
+
+impl<T: DeserializeArray + Clone> DeserializeArray for Cow<'_, T> {
+    fn deserialize_array(parcel: &BorrowedParcel<'_>) -> Result<Option<Vec<Self>>> {
+        let vec = T::deserialize_array(parcel)?;
+        Ok(vec.map(|vec| vec.into_iter().map(Cow::Owned).collect()))
+    }
+
+    fn deserialize_array_element(parcel: &BorrowedParcel<'_>, index: usize) -> Result<Self> {
+        T::deserialize_array_element(parcel, index).map(Cow::Owned)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::parcel::Parcel;
@@ -1218,4 +1636,47 @@ mod tests {
 
         assert_eq!(vec, strs);
     }
+
+    #[test]
+    fn test_shared_pointers() {
+        fn marshal<T: Serialize + ?Sized>(value: &T) -> Vec<u8> {
+            let mut parcel = Parcel::new();
+            parcel.write(value).unwrap();
+            parcel.marshal().unwrap()
+        }
+
+        // Pointers must be written exactly like their contents, including the
+        // packed layout of byte arrays.
+        let bytes: Vec<u8> = vec![1, 2, 3, 4, 5];
+        let shared_bytes: Vec<Arc<u8>> = bytes.iter().copied().map(Arc::new).collect();
+        assert_eq!(marshal(&shared_bytes), marshal(&bytes));
+        assert_eq!(marshal(&Rc::new(bytes.clone())), marshal(&bytes));
+        let cow_bytes: Vec<Cow<'_, u8>> = bytes.iter().map(Cow::Borrowed).collect();
+        assert_eq!(marshal(&cow_bytes), marshal(&bytes));
+        assert_eq!(marshal(&Cow::Borrowed(&bytes[..])), marshal(&bytes));
+
+        let strings = vec![Some("a".to_string()), None];
+        let shared_strings: Vec<Option<Rc<String>>> =
+            strings.iter().cloned().map(|s| s.map(Rc::new)).collect();
+        assert_eq!(marshal(&shared_strings), marshal(&strings));
+        assert_eq!(marshal(&Arc::<str>::from("text")), marshal("text"));
+        assert_eq!(marshal(&None::<Arc<String>>), marshal(&None::<String>));
+
+        let mut parcel = Parcel::new();
+        parcel.write(&Arc::new(42i32)).unwrap();
+        parcel.write(&shared_bytes).unwrap();
+        parcel.write(&Some(Rc::new("rc".to_string()))).unwrap();
+        parcel.write(&Cow::Borrowed("cow")).unwrap();
+        parcel.write(&None::<Vec<Arc<i64>>>).unwrap();
+        parcel.write_array_iter(bytes.iter().copied().map(Rc::new)).unwrap();
+
+        parcel.rewind_to_start().unwrap();
+        assert_eq!(parcel.read::<Arc<i32>>(), Ok(Arc::new(42)));
+        assert_eq!(parcel.read::<Vec<Arc<u8>>>(), Ok(shared_bytes));
+        assert_eq!(parcel.read::<Option<Rc<String>>>(), Ok(Some(Rc::new("rc".to_string()))));
+        assert_eq!(parcel.read::<Cow<'_, str>>(), Ok(Cow::Borrowed("cow")));
+        assert_eq!(parcel.read::<Option<Vec<Arc<i64>>>>(), Ok(None));
+        let iter = parcel.read_array_iter::<Rc<u8>>().unwrap();
+        assert_eq!(iter.collect::<Result<Vec<_>>>(), Ok(bytes.into_iter().map(Rc::new).collect()));
+    }
 }
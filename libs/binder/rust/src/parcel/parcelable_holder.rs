@@ -59,9 +59,9 @@ enum ParcelableHolderData {
 ///
 /// This type is currently used for AIDL parcelable fields.
 ///
-/// `ParcelableHolder` is currently not thread-safe (neither
-/// `Send` nor `Sync`), mainly because it internally contains
-/// a `Parcel` which in turn is not thread-safe.
+/// `ParcelableHolder` is both `Send` and `Sync`: stored parcelables must be
+/// `Send + Sync`, and the unparsed data of a received holder is kept in a
+/// `Parcel` behind a `Mutex`.
 #[derive(Debug)]
 pub struct ParcelableHolder {
     // This is a `Mutex` because of `get_parcelable`
@@ -144,10 +144,7 @@ impl ParcelableHolder {
                 }
             }
             ParcelableHolderData::Parcel(ref mut parcel) => {
-                unsafe {
-                    // Safety: 0 should always be a valid position.
-                    parcel.set_data_position(0)?;
-                }
+                parcel.rewind_to_start()?;
 
                 let name: String = parcel.read()?;
                 if name != parcelable_desc {
@@ -170,6 +167,75 @@ impl ParcelableHolder {
     pub fn get_stability(&self) -> Stability {
         self.stability
     }
+
+    /// Returns `true` if this `ParcelableHolder` does not contain a
+    /// parcelable.
+    pub fn is_empty(&self) -> bool {
+        matches!(*self.data.lock().unwrap(), ParcelableHolderData::Empty)
+    }
+
+    /// Retrieve the descriptor of the parcelable stored in this
+    /// `ParcelableHolder`, without knowing its type.
+    ///
+    /// Returns `Ok(None)` if the holder is empty. For a holder that was read
+    /// from a parcel, the descriptor is read from its unparsed data.
+    pub fn get_descriptor(&self) -> Result<Option<String>, StatusCode> {
+        let mut data = self.data.lock().unwrap();
+        match *data {
+            ParcelableHolderData::Empty => Ok(None),
+            ParcelableHolderData::Parcelable { ref name, .. } => Ok(Some(name.clone())),
+            ParcelableHolderData::Parcel(ref mut parcel) => {
+                parcel.rewind_to_start()?;
+                parcel.read().map(Some)
+            }
+        }
+    }
+
+    /// Retrieve the raw parcel data of the parcelable stored in this
+    /// `ParcelableHolder`, as returned by [`Parcel::marshal`].
+    ///
+    /// The data is the descriptor followed by the parcelable itself, which
+    /// is what gets sent as the contents of the holder. Returns `Ok(None)` if
+    /// the holder is empty, and fails with `INVALID_OPERATION` if the
+    /// parcelable contains binders or file descriptors.
+    pub fn get_raw_data(&self) -> Result<Option<Vec<u8>>, StatusCode> {
+        let data = self.data.lock().unwrap();
+        match *data {
+            ParcelableHolderData::Empty => Ok(None),
+            ParcelableHolderData::Parcelable {
+                ref parcelable,
+                ref name,
+            } => {
+                let mut parcel = Parcel::new();
+                parcel.write(name)?;
+                parcelable.write_to_parcel(&mut parcel.borrowed())?;
+                parcel.marshal().map(Some)
+            }
+            ParcelableHolderData::Parcel(ref parcel) => parcel.marshal().map(Some),
+        }
+    }
+}
+
+/// Generates holders with the default stability, which is what the
+/// `Deserialize` implementation expects, holding either nothing or an
+/// arbitrary descriptor followed by arbitrary parcelable data.
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for ParcelableHolder {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let mut holder = ParcelableHolder::new(Default::default());
+        if u.arbitrary()? {
+            let name: String = u.arbitrary()?;
+            let words: Vec<i32> = u.arbitrary()?;
+
+            let mut parcel = Parcel::new();
+            parcel
+                .write(&name)
+                .and_then(|_| words.iter().try_for_each(|word| parcel.write(word)))
+                .map_err(|_| arbitrary::Error::IncorrectFormat)?;
+            *holder.data.get_mut().unwrap() = ParcelableHolderData::Parcel(parcel);
+        }
+        Ok(holder)
+    }
 }
 
 impl Serialize for ParcelableHolder {
@@ -203,27 +269,19 @@ impl Parcelable for ParcelableHolder {
                 ref parcelable,
                 ref name,
             } => {
-                let length_start = parcel.get_data_position();
+                let length_start = parcel.checkpoint();
                 parcel.write(&0i32)?;
 
-                let data_start = parcel.get_data_position();
+                let data_start = parcel.checkpoint();
                 parcel.write(name)?;
                 parcelable.write_to_parcel(parcel)?;
 
-                let end = parcel.get_data_position();
-                unsafe {
-                    // Safety: we got the position from `get_data_position`.
-                    parcel.set_data_position(length_start)?;
-                }
-
-                assert!(end >= data_start);
-                parcel.write(&(end - data_start))?;
-                unsafe {
-                    // Safety: we got the position from `get_data_position`.
-                    parcel.set_data_position(end)?;
-                }
+                let end = parcel.checkpoint();
+                parcel.rewind(length_start)?;
 
-                Ok(())
+                assert!(end.position() >= data_start.position());
+                parcel.write(&(end.position() - data_start.position()))?;
+                parcel.rewind(end)
             }
             ParcelableHolderData::Parcel(ref mut p) => {
                 parcel.write(&p.get_data_size())?;
@@ -270,3 +328,70 @@ impl Parcelable for ParcelableHolder {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Default, PartialEq)]
+    struct Point {
+        x: i32,
+        y: i32,
+    }
+
+    impl Parcelable for Point {
+        fn write_to_parcel(&self, parcel: &mut BorrowedParcel<'_>) -> Result<(), StatusCode> {
+            parcel.write(&self.x)?;
+            parcel.write(&self.y)
+        }
+
+        fn read_from_parcel(&mut self, parcel: &BorrowedParcel<'_>) -> Result<(), StatusCode> {
+            self.x = parcel.read()?;
+            self.y = parcel.read()?;
+            Ok(())
+        }
+    }
+
+    impl ParcelableMetadata for Point {
+        fn get_descriptor() -> &'static str {
+            "android.test.Point"
+        }
+    }
+
+    #[test]
+    fn test_holder_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<ParcelableHolder>();
+    }
+
+    #[test]
+    fn test_holder_introspection() {
+        let mut holder = ParcelableHolder::new(Stability::Local);
+        assert!(holder.is_empty());
+        assert_eq!(holder.get_descriptor(), Ok(None));
+        assert_eq!(holder.get_raw_data(), Ok(None));
+
+        holder.set_parcelable(Arc::new(Point { x: 1, y: 2 })).unwrap();
+        assert!(!holder.is_empty());
+        assert_eq!(holder.get_descriptor(), Ok(Some("android.test.Point".to_string())));
+        let raw_data = holder.get_raw_data().unwrap().unwrap();
+
+        // A holder read from a parcel keeps the data unparsed until it is
+        // retrieved with a concrete type, but can still be inspected.
+        let mut parcel = Parcel::new();
+        parcel.write(&holder).unwrap();
+        parcel.rewind_to_start().unwrap();
+        let received: ParcelableHolder = parcel.read().unwrap();
+        assert!(!received.is_empty());
+        assert_eq!(received.get_descriptor(), Ok(Some("android.test.Point".to_string())));
+        assert_eq!(received.get_raw_data(), Ok(Some(raw_data)));
+        assert_eq!(received.get_stability(), Stability::Local);
+        assert_eq!(
+            received.get_parcelable::<Point>().unwrap().as_deref(),
+            Some(&Point { x: 1, y: 2 })
+        );
+
+        holder.reset();
+        assert!(holder.is_empty());
+    }
+}
@@ -0,0 +1,296 @@
+/*
+ * Copyright (C) 2022 The Android Open Source Project
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Decoding of `ParcelableHolder` contents by descriptor.
+
+use crate::error::StatusCode;
+use crate::parcel::{Parcelable, ParcelableHolder, ParcelableMetadata};
+
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// A parcelable decoded by a [`ParcelableRegistry`].
+///
+/// This holds an `Arc<T>` for the registered type `T`, as returned by
+/// [`ParcelableHolder::get_parcelable`], so it must be downcast to `Arc<T>`
+/// rather than `T`. [`ParcelableRegistry::decode_as`] does this directly.
+pub type DecodedParcelable = Box<dyn Any + Send + Sync>;
+
+type DecodeFn = fn(&ParcelableHolder) -> Result<Option<DecodedParcelable>, StatusCode>;
+type DebugFn = fn(&DecodedParcelable, &mut fmt::Formatter<'_>) -> fmt::Result;
+
+#[derive(Copy, Clone)]
+struct Entry {
+    decode: DecodeFn,
+    debug: DebugFn,
+}
+
+fn decode<T>(holder: &ParcelableHolder) -> Result<Option<DecodedParcelable>, StatusCode>
+where
+    T: Any + Parcelable + ParcelableMetadata + Default + fmt::Debug + Send + Sync,
+{
+    Ok(holder.get_parcelable::<T>()?.map(|p| Box::new(p) as DecodedParcelable))
+}
+
+fn debug<T>(value: &DecodedParcelable, f: &mut fmt::Formatter<'_>) -> fmt::Result
+where
+    T: Any + fmt::Debug,
+{
+    match value.downcast_ref::<Arc<T>>() {
+        Some(value) => fmt::Debug::fmt(value, f),
+        None => f.write_str("<unknown>"),
+    }
+}
+
+/// A set of parcelable types that a `ParcelableHolder` may contain, such as
+/// the extensions of an AIDL interface.
+///
+/// The registry decodes the contents of a holder according to their
+/// descriptor, so that callers don't need to try every possible type with
+/// [`ParcelableHolder::get_parcelable`].
+#[derive(Clone, Default)]
+pub struct ParcelableRegistry {
+    entries: BTreeMap<&'static str, Entry>,
+}
+
+impl ParcelableRegistry {
+    /// Create a new, empty registry.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Register the parcelable type `T` under its descriptor, replacing any
+    /// type previously registered with the same descriptor.
+    pub fn register<T>(&mut self) -> &mut Self
+    where
+        T: Any + Parcelable + ParcelableMetadata + Default + fmt::Debug + Send + Sync,
+    {
+        let entry = Entry { decode: decode::<T>, debug: debug::<T> };
+        self.entries.insert(T::get_descriptor(), entry);
+        self
+    }
+
+    /// Returns `true` if a type is registered for `descriptor`.
+    pub fn is_registered(&self, descriptor: &str) -> bool {
+        self.entries.contains_key(descriptor)
+    }
+
+    /// Returns the descriptors of all registered types, in sorted order.
+    pub fn descriptors(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.entries.keys().copied()
+    }
+
+    /// Decode the parcelable stored in `holder` according to its descriptor.
+    ///
+    /// On success, the returned value can be downcast to `Arc<T>`, where `T`
+    /// is the type registered for the descriptor. Returns `Ok(None)` if the
+    /// holder is empty or no type is registered for its descriptor.
+    pub fn decode(
+        &self,
+        holder: &ParcelableHolder,
+    ) -> Result<Option<DecodedParcelable>, StatusCode> {
+        match self.lookup(holder.get_descriptor()?.as_deref()) {
+            Some(entry) => (entry.decode)(holder),
+            None => Ok(None),
+        }
+    }
+
+    /// Decode the parcelable stored in `holder` if it has the type `T`.
+    ///
+    /// Returns `Ok(None)` if the holder is empty, no type is registered for
+    /// its descriptor, or the registered type is not `T`.
+    pub fn decode_as<T: Any + Send + Sync>(
+        &self,
+        holder: &ParcelableHolder,
+    ) -> Result<Option<Arc<T>>, StatusCode> {
+        let decoded = self.decode(holder)?;
+        Ok(decoded.and_then(|value| value.downcast::<Arc<T>>().ok()).map(|value| *value))
+    }
+
+    /// Returns a view of `holder` whose `Debug` implementation shows the
+    /// decoded parcelable, if its type is registered.
+    pub fn debug<'a>(&'a self, holder: &'a ParcelableHolder) -> DecodedHolder<'a> {
+        DecodedHolder { registry: self, holder }
+    }
+
+    fn lookup(&self, descriptor: Option<&str>) -> Option<&Entry> {
+        descriptor.and_then(|descriptor| self.entries.get(descriptor))
+    }
+}
+
+impl fmt::Debug for ParcelableRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.descriptors()).finish()
+    }
+}
+
+/// A `ParcelableHolder` along with a [`ParcelableRegistry`] to decode it, for
+/// debugging.
+///
+/// Returned by [`ParcelableRegistry::debug`].
+pub struct DecodedHolder<'a> {
+    registry: &'a ParcelableRegistry,
+    holder: &'a ParcelableHolder,
+}
+
+/// Formats a decoded parcelable with the `Debug` implementation of its type.
+struct DebugDecoded(DecodedParcelable, DebugFn);
+
+impl fmt::Debug for DebugDecoded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (self.1)(&self.0, f)
+    }
+}
+
+impl<'a> fmt::Debug for DecodedHolder<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("ParcelableHolder");
+        debug.field("stability", &self.holder.get_stability());
+        let descriptor = match self.holder.get_descriptor() {
+            Ok(descriptor) => descriptor,
+            Err(e) => return debug.field("error", &e).finish(),
+        };
+        debug.field("descriptor", &descriptor);
+        match self.registry.lookup(descriptor.as_deref()) {
+            Some(entry) => match (entry.decode)(self.holder) {
+                Ok(Some(value)) => debug.field("parcelable", &DebugDecoded(value, entry.debug)),
+                Ok(None) => debug.field("parcelable", &None::<()>),
+                Err(e) => debug.field("error", &e),
+            },
+            None if !self.holder.is_empty() => {
+                debug.field("parcelable", &format_args!("<unregistered>"))
+            }
+            None => debug.field("parcelable", &None::<()>),
+        };
+        debug.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binder::Stability;
+    use crate::error::Result;
+    use crate::parcel::{BorrowedParcel, Parcel};
+
+    #[derive(Debug, Default, PartialEq)]
+    struct Temperature(i32);
+
+    impl Parcelable for Temperature {
+        fn write_to_parcel(&self, parcel: &mut BorrowedParcel<'_>) -> Result<()> {
+            parcel.write(&self.0)
+        }
+
+        fn read_from_parcel(&mut self, parcel: &BorrowedParcel<'_>) -> Result<()> {
+            self.0 = parcel.read()?;
+            Ok(())
+        }
+    }
+
+    impl ParcelableMetadata for Temperature {
+        fn get_descriptor() -> &'static str {
+            "android.test.Temperature"
+        }
+    }
+
+    #[derive(Debug, Default, PartialEq)]
+    struct Label(String);
+
+    impl Parcelable for Label {
+        fn write_to_parcel(&self, parcel: &mut BorrowedParcel<'_>) -> Result<()> {
+            parcel.write(&self.0)
+        }
+
+        fn read_from_parcel(&mut self, parcel: &BorrowedParcel<'_>) -> Result<()> {
+            self.0 = parcel.read()?;
+            Ok(())
+        }
+    }
+
+    impl ParcelableMetadata for Label {
+        fn get_descriptor() -> &'static str {
+            "android.test.Label"
+        }
+    }
+
+    fn received(holder: &ParcelableHolder) -> ParcelableHolder {
+        let mut parcel = Parcel::new();
+        parcel.write(holder).unwrap();
+        parcel.rewind_to_start().unwrap();
+        parcel.read().unwrap()
+    }
+
+    #[test]
+    fn test_registry_decode() {
+        let mut registry = ParcelableRegistry::new();
+        registry.register::<Temperature>().register::<Label>();
+        assert!(registry.is_registered("android.test.Label"));
+        assert_eq!(
+            registry.descriptors().collect::<Vec<_>>(),
+            ["android.test.Label", "android.test.Temperature"]
+        );
+
+        let mut holder = ParcelableHolder::new(Stability::Local);
+        assert!(registry.decode(&holder).unwrap().is_none());
+
+        holder.set_parcelable(Arc::new(Label("kitchen".to_string()))).unwrap();
+        let decoded = registry.decode(&received(&holder)).unwrap().unwrap();
+        let label = decoded.downcast::<Arc<Label>>().unwrap();
+        assert_eq!(**label, Label("kitchen".to_string()));
+
+        holder.set_parcelable(Arc::new(Temperature(21))).unwrap();
+        let decoded = registry.decode(&holder).unwrap().unwrap();
+        assert_eq!(decoded.downcast_ref::<Arc<Temperature>>().map(|t| t.0), Some(21));
+        assert!(!decoded.is::<Temperature>());
+        assert_eq!(
+            registry.decode_as::<Temperature>(&holder).unwrap(),
+            Some(Arc::new(Temperature(21)))
+        );
+        assert_eq!(registry.decode_as::<Label>(&holder).unwrap(), None);
+
+        let unregistered = ParcelableRegistry::new();
+        assert!(unregistered.decode(&holder).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_registry_debug() {
+        let mut registry = ParcelableRegistry::new();
+        registry.register::<Temperature>();
+        assert_eq!(format!("{:?}", registry), r#"{"android.test.Temperature"}"#);
+
+        let mut holder = ParcelableHolder::new(Stability::Local);
+        assert_eq!(
+            format!("{:?}", registry.debug(&holder)),
+            "ParcelableHolder { stability: Local, descriptor: None, parcelable: None }"
+        );
+
+        holder.set_parcelable(Arc::new(Temperature(-4))).unwrap();
+        assert_eq!(
+            format!("{:?}", registry.debug(&received(&holder))),
+            "ParcelableHolder { stability: Local, descriptor: Some(\"android.test.Temperature\"), \
+             parcelable: Temperature(-4) }"
+        );
+
+        holder.set_parcelable(Arc::new(Label("hall".to_string()))).unwrap();
+        assert_eq!(
+            format!("{:?}", registry.debug(&holder)),
+            "ParcelableHolder { stability: Local, descriptor: Some(\"android.test.Label\"), \
+             parcelable: <unregistered> }"
+        );
+    }
+}
@@ -0,0 +1,314 @@
+/*
+ * Copyright (C) 2022 The Android Open Source Project
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! A versioned file format for persisting parcelables.
+//!
+//! A persisted parcelable is stored as a header followed by the marshalled
+//! parcel data. All integers are little-endian `u32`s:
+//!
+//! | Field              | Size                |
+//! |--------------------|---------------------|
+//! | Magic (`"BPCL"`)   | 4                   |
+//! | Format version     | 4                   |
+//! | Parcelable version | 4                   |
+//! | Descriptor length  | 4                   |
+//! | Descriptor (UTF-8) | descriptor length   |
+//! | Data length        | 4                   |
+//! | Parcel data        | data length         |
+//!
+//! The parcelable version is chosen by the application, and lets readers
+//! reject data written by an incompatible version of the parcelable.
+
+use crate::error::StatusCode;
+use crate::parcel::{Parcel, Parcelable, ParcelableMetadata};
+
+use std::convert::TryFrom;
+use std::error;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::ops::RangeInclusive;
+
+const MAGIC: [u8; 4] = *b"BPCL";
+const FORMAT_VERSION: u32 = 1;
+
+/// Maximum length of a persisted descriptor, to avoid allocating arbitrary
+/// amounts of memory for a corrupted header.
+const MAX_DESCRIPTOR_LEN: u32 = 1024;
+
+/// Errors from reading or writing a persisted parcelable.
+#[derive(Debug)]
+pub enum PersistError {
+    /// Reading or writing the underlying stream failed.
+    Io(io::Error),
+
+    /// Serializing or deserializing the parcelable failed.
+    Parcel(StatusCode),
+
+    /// The data does not start with a valid header.
+    InvalidHeader,
+
+    /// The data was written in a version of the file format other than the
+    /// one this reader implements.
+    UnsupportedVersion(u32),
+
+    /// The data holds a different parcelable type than the one requested.
+    DescriptorMismatch {
+        /// Descriptor of the requested parcelable type.
+        expected: &'static str,
+        /// Descriptor found in the data.
+        found: String,
+    },
+
+    /// The data was written with a parcelable version that the reader does
+    /// not support.
+    IncompatibleVersion(u32),
+}
+
+impl fmt::Display for PersistError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PersistError::Io(e) => write!(f, "I/O error: {}", e),
+            PersistError::Parcel(e) => write!(f, "parcel error: {:?}", e),
+            PersistError::InvalidHeader => f.write_str("invalid persisted parcelable header"),
+            PersistError::UnsupportedVersion(v) => write!(f, "unsupported format version {}", v),
+            PersistError::DescriptorMismatch { expected, found } => {
+                write!(f, "expected parcelable {}, found {}", expected, found)
+            }
+            PersistError::IncompatibleVersion(v) => {
+                write!(f, "incompatible parcelable version {}", v)
+            }
+        }
+    }
+}
+
+impl error::Error for PersistError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            PersistError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for PersistError {
+    fn from(e: io::Error) -> Self {
+        PersistError::Io(e)
+    }
+}
+
+impl From<StatusCode> for PersistError {
+    fn from(e: StatusCode) -> Self {
+        PersistError::Parcel(e)
+    }
+}
+
+/// Write a parcelable with its descriptor and the given version to `writer`.
+///
+/// The parcelable must not contain any binders or file descriptors.
+pub fn write_persistent<T, W>(
+    parcelable: &T,
+    version: u32,
+    writer: &mut W,
+) -> Result<(), PersistError>
+where
+    T: Parcelable + ParcelableMetadata,
+    W: Write + ?Sized,
+{
+    let mut parcel = Parcel::new();
+    parcelable.write_to_parcel(&mut parcel.borrowed())?;
+    let data = parcel.marshal()?;
+
+    let descriptor = T::get_descriptor().as_bytes();
+    writer.write_all(&MAGIC)?;
+    write_u32(writer, FORMAT_VERSION)?;
+    write_u32(writer, version)?;
+    write_len(writer, descriptor.len())?;
+    writer.write_all(descriptor)?;
+    write_len(writer, data.len())?;
+    writer.write_all(&data)?;
+    Ok(())
+}
+
+/// Read a parcelable written by [`write_persistent`] from `reader`, returning
+/// it along with its version.
+///
+/// Fails if the data holds a different parcelable type, or if its version is
+/// not in `supported_versions`.
+pub fn read_persistent<T, R>(
+    reader: &mut R,
+    supported_versions: RangeInclusive<u32>,
+) -> Result<(T, u32), PersistError>
+where
+    T: Parcelable + ParcelableMetadata + Default,
+    R: Read + ?Sized,
+{
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    if magic != MAGIC {
+        return Err(PersistError::InvalidHeader);
+    }
+    let format = read_u32(reader)?;
+    if format != FORMAT_VERSION {
+        return Err(PersistError::UnsupportedVersion(format));
+    }
+    let version = read_u32(reader)?;
+
+    let descriptor_len = read_u32(reader)?;
+    if descriptor_len > MAX_DESCRIPTOR_LEN {
+        return Err(PersistError::InvalidHeader);
+    }
+    let descriptor = read_bytes(reader, descriptor_len)?;
+    if descriptor != T::get_descriptor().as_bytes() {
+        return Err(PersistError::DescriptorMismatch {
+            expected: T::get_descriptor(),
+            found: String::from_utf8_lossy(&descriptor).into_owned(),
+        });
+    }
+    if !supported_versions.contains(&version) {
+        return Err(PersistError::IncompatibleVersion(version));
+    }
+
+    let data_len = read_u32(reader)?;
+    let data = read_bytes(reader, data_len)?;
+    let parcel = Parcel::unmarshal(&data)?;
+    let mut parcelable = T::default();
+    parcelable.read_from_parcel(parcel.borrowed_ref())?;
+    Ok((parcelable, version))
+}
+
+fn write_u32<W: Write + ?Sized>(writer: &mut W, value: u32) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+fn write_len<W: Write + ?Sized>(writer: &mut W, len: usize) -> Result<(), PersistError> {
+    let len = u32::try_from(len).or(Err(StatusCode::BAD_VALUE))?;
+    Ok(write_u32(writer, len)?)
+}
+
+fn read_u32<R: Read + ?Sized>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+/// Read exactly `len` bytes, without trusting `len` for the allocation size.
+fn read_bytes<R: Read + ?Sized>(reader: &mut R, len: u32) -> io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    reader.take(len.into()).read_to_end(&mut bytes)?;
+    if bytes.len() != len as usize {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Result;
+    use crate::parcel::BorrowedParcel;
+
+    #[derive(Debug, Default, PartialEq)]
+    struct Settings {
+        brightness: i32,
+        name: String,
+    }
+
+    impl Parcelable for Settings {
+        fn write_to_parcel(&self, parcel: &mut BorrowedParcel<'_>) -> Result<()> {
+            parcel.write(&self.brightness)?;
+            parcel.write(&self.name)
+        }
+
+        fn read_from_parcel(&mut self, parcel: &BorrowedParcel<'_>) -> Result<()> {
+            self.brightness = parcel.read()?;
+            self.name = parcel.read()?;
+            Ok(())
+        }
+    }
+
+    impl ParcelableMetadata for Settings {
+        fn get_descriptor() -> &'static str {
+            "android.test.Settings"
+        }
+    }
+
+    #[derive(Debug, Default)]
+    struct Other;
+
+    impl Parcelable for Other {
+        fn write_to_parcel(&self, _parcel: &mut BorrowedParcel<'_>) -> Result<()> {
+            Ok(())
+        }
+
+        fn read_from_parcel(&mut self, _parcel: &BorrowedParcel<'_>) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    impl ParcelableMetadata for Other {
+        fn get_descriptor() -> &'static str {
+            "android.test.Other"
+        }
+    }
+
+    #[test]
+    fn test_persist_round_trip() {
+        let settings = Settings { brightness: 80, name: "living room".to_string() };
+        let mut data = Vec::new();
+        write_persistent(&settings, 3, &mut data).unwrap();
+        assert_eq!(&data[..4], b"BPCL");
+
+        let (read, version) = read_persistent::<Settings, _>(&mut &data[..], 1..=3).unwrap();
+        assert_eq!(read, settings);
+        assert_eq!(version, 3);
+    }
+
+    #[test]
+    fn test_persist_compatibility() {
+        let settings = Settings { brightness: 10, name: "hall".to_string() };
+        let mut data = Vec::new();
+        write_persistent(&settings, 4, &mut data).unwrap();
+
+        assert!(matches!(
+            read_persistent::<Settings, _>(&mut &data[..], 1..=3),
+            Err(PersistError::IncompatibleVersion(4))
+        ));
+        assert!(matches!(
+            read_persistent::<Other, _>(&mut &data[..], 1..=4),
+            Err(PersistError::DescriptorMismatch { found, .. }) if found == "android.test.Settings"
+        ));
+        assert!(matches!(
+            read_persistent::<Settings, _>(&mut &data[..data.len() - 1], 1..=4),
+            Err(PersistError::Io(_))
+        ));
+
+        let mut corrupted = data.clone();
+        corrupted[0] = b'X';
+        assert!(matches!(
+            read_persistent::<Settings, _>(&mut &corrupted[..], 1..=4),
+            Err(PersistError::InvalidHeader)
+        ));
+
+        for format in [0, FORMAT_VERSION + 1] {
+            let mut other = data.clone();
+            other[4..8].copy_from_slice(&format.to_le_bytes());
+            assert!(matches!(
+                read_persistent::<Settings, _>(&mut &other[..], 1..=4),
+                Err(PersistError::UnsupportedVersion(v)) if v == format
+            ));
+        }
+    }
+}
@@ -0,0 +1,354 @@
+/*
+ * Copyright (C) 2022 The Android Open Source Project
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use super::{limits, BorrowedParcel, Parcelable, ParcelableMetadata};
+use crate::error::{Result, StatusCode};
+
+use std::collections::BTreeMap;
+use std::convert::TryInto;
+
+// Keep these in sync with BUNDLE_MAGIC* in
+// frameworks/base/core/java/android/os/BaseBundle.java.
+const BUNDLE_MAGIC: i32 = 0x4C444E42;
+const BUNDLE_MAGIC_NATIVE: i32 = 0x4C444E44;
+
+// Value types, from frameworks/base/core/java/android/os/Parcel.java.
+const VAL_STRING: i32 = 0;
+const VAL_INTEGER: i32 = 1;
+const VAL_LONG: i32 = 6;
+const VAL_DOUBLE: i32 = 8;
+const VAL_BOOLEAN: i32 = 9;
+const VAL_STRINGARRAY: i32 = 14;
+const VAL_INTARRAY: i32 = 18;
+const VAL_LONGARRAY: i32 = 19;
+const VAL_BOOLEANARRAY: i32 = 23;
+const VAL_PERSISTABLEBUNDLE: i32 = 25;
+const VAL_DOUBLEARRAY: i32 = 28;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Bool(bool),
+    Int(i32),
+    Long(i64),
+    Double(f64),
+    String(String),
+    BoolVec(Vec<bool>),
+    IntVec(Vec<i32>),
+    LongVec(Vec<i64>),
+    DoubleVec(Vec<f64>),
+    StringVec(Vec<String>),
+    PersistableBundle(PersistableBundle),
+}
+
+/// Rust version of the Java class android.os.PersistableBundle
+///
+/// A map from string keys to values of a few types that can be persisted,
+/// with the same wire format as in Java and C++. Each key holds at most one
+/// value; inserting a value replaces any existing value for the same key,
+/// whatever its type.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct PersistableBundle {
+    entries: BTreeMap<String, Value>,
+}
+
+impl PersistableBundle {
+    /// Create a new, empty `PersistableBundle`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Returns the number of entries in the bundle.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if the bundle has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns `true` if the bundle has a value of any type for `key`.
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    /// Returns the keys of all entries in the bundle, in sorted order.
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(String::as_str)
+    }
+
+    /// Remove the value for `key`, returning whether there was one.
+    pub fn remove(&mut self, key: &str) -> bool {
+        self.entries.remove(key).is_some()
+    }
+
+    /// Remove all entries from the bundle.
+    pub fn clear(&mut self) {
+        self.entries.clear()
+    }
+
+    fn write_entries(&self, parcel: &mut BorrowedParcel<'_>) -> Result<()> {
+        let len: i32 = self.entries.len().try_into().or(Err(StatusCode::BAD_VALUE))?;
+        parcel.write(&len)?;
+        for (key, value) in &self.entries {
+            parcel.write(key)?;
+            match value {
+                Value::Bool(v) => write_value(parcel, VAL_BOOLEAN, v)?,
+                Value::Int(v) => write_value(parcel, VAL_INTEGER, v)?,
+                Value::Long(v) => write_value(parcel, VAL_LONG, v)?,
+                Value::Double(v) => write_value(parcel, VAL_DOUBLE, v)?,
+                Value::String(v) => write_value(parcel, VAL_STRING, v)?,
+                Value::BoolVec(v) => write_value(parcel, VAL_BOOLEANARRAY, v)?,
+                Value::IntVec(v) => write_value(parcel, VAL_INTARRAY, v)?,
+                Value::LongVec(v) => write_value(parcel, VAL_LONGARRAY, v)?,
+                Value::DoubleVec(v) => write_value(parcel, VAL_DOUBLEARRAY, v)?,
+                Value::StringVec(v) => write_value(parcel, VAL_STRINGARRAY, v)?,
+                Value::PersistableBundle(v) => {
+                    parcel.write(&VAL_PERSISTABLEBUNDLE)?;
+                    v.write_to_parcel(parcel)?
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn read_entries(&mut self, parcel: &BorrowedParcel<'_>) -> Result<()> {
+        let len: i32 = parcel.read()?;
+        limits::check_array_len(len)?;
+        for _ in 0..len {
+            let key: String = parcel.read()?;
+            let value = match parcel.read()? {
+                VAL_BOOLEAN => Value::Bool(parcel.read()?),
+                VAL_INTEGER => Value::Int(parcel.read()?),
+                VAL_LONG => Value::Long(parcel.read()?),
+                VAL_DOUBLE => Value::Double(parcel.read()?),
+                VAL_STRING => Value::String(parcel.read()?),
+                VAL_BOOLEANARRAY => Value::BoolVec(parcel.read()?),
+                VAL_INTARRAY => Value::IntVec(parcel.read()?),
+                VAL_LONGARRAY => Value::LongVec(parcel.read()?),
+                VAL_DOUBLEARRAY => Value::DoubleVec(parcel.read()?),
+                VAL_STRINGARRAY => Value::StringVec(parcel.read()?),
+                VAL_PERSISTABLEBUNDLE => limits::nested(|| {
+                    let mut bundle = PersistableBundle::new();
+                    bundle.read_from_parcel(parcel)?;
+                    Ok(Value::PersistableBundle(bundle))
+                })?,
+                _ => return Err(StatusCode::BAD_TYPE),
+            };
+            self.entries.insert(key, value);
+        }
+        Ok(())
+    }
+}
+
+fn write_value<T: super::Serialize + ?Sized>(
+    parcel: &mut BorrowedParcel<'_>,
+    value_type: i32,
+    value: &T,
+) -> Result<()> {
+    parcel.write(&value_type)?;
+    parcel.write(value)
+}
+
+/// Implement a typed setter and getter for each kind of value.
+macro_rules! typed_accessors {
+    {
+        $(
+            $variant:ident($owned:ty) as $borrowed:ty, $name:literal:
+            $insert:ident, $get:ident, |$value:ident| $borrow:expr;
+        )*
+    } => {
+        impl PersistableBundle {
+            $(
+                #[doc = concat!("Insert ", $name, " value for `key`, replacing any existing ")]
+                #[doc = "value."]
+                pub fn $insert(&mut self, key: impl Into<String>, value: $owned) {
+                    self.entries.insert(key.into(), Value::$variant(value));
+                }
+
+                #[doc = concat!("Returns the ", $name, " value for `key`, or `None` if there is ")]
+                #[doc = "no value for `key` or it has a different type."]
+                pub fn $get(&self, key: &str) -> Option<$borrowed> {
+                    match self.entries.get(key) {
+                        Some(Value::$variant($value)) => Some($borrow),
+                        _ => None,
+                    }
+                }
+            )*
+        }
+    };
+}
+
+typed_accessors! {
+    Bool(bool) as bool, "a `bool`": insert_bool, get_bool, |v| *v;
+    Int(i32) as i32, "an `i32`": insert_int, get_int, |v| *v;
+    Long(i64) as i64, "an `i64`": insert_long, get_long, |v| *v;
+    Double(f64) as f64, "an `f64`": insert_double, get_double, |v| *v;
+    String(String) as &str, "a string": insert_string, get_string, |v| v.as_str();
+    BoolVec(Vec<bool>) as &[bool], "a `bool` array":
+        insert_bool_vec, get_bool_vec, |v| v.as_slice();
+    IntVec(Vec<i32>) as &[i32], "an `i32` array": insert_int_vec, get_int_vec, |v| v.as_slice();
+    LongVec(Vec<i64>) as &[i64], "an `i64` array": insert_long_vec, get_long_vec, |v| v.as_slice();
+    DoubleVec(Vec<f64>) as &[f64], "an `f64` array":
+        insert_double_vec, get_double_vec, |v| v.as_slice();
+    StringVec(Vec<String>) as &[String], "a string array":
+        insert_string_vec, get_string_vec, |v| v.as_slice();
+    PersistableBundle(PersistableBundle) as &PersistableBundle, "a nested bundle":
+        insert_persistable_bundle, get_persistable_bundle, |v| v;
+}
+
+impl Parcelable for PersistableBundle {
+    // Keep in sync with writeToParcelInner() in
+    // frameworks/base/core/java/android/os/BaseBundle.java.
+    fn write_to_parcel(&self, parcel: &mut BorrowedParcel<'_>) -> Result<()> {
+        if self.is_empty() {
+            return parcel.write(&0i32);
+        }
+
+        let length_start = parcel.checkpoint();
+        parcel.write(&0i32)?;
+        parcel.write(&BUNDLE_MAGIC_NATIVE)?;
+
+        let data_start = parcel.checkpoint();
+        self.write_entries(parcel)?;
+
+        // The length covers the data after the magic number.
+        let end = parcel.checkpoint();
+        parcel.rewind(length_start)?;
+        parcel.write(&(end.position() - data_start.position()))?;
+        parcel.rewind(end)
+    }
+
+    fn read_from_parcel(&mut self, parcel: &BorrowedParcel<'_>) -> Result<()> {
+        let length: i32 = parcel.read()?;
+        if length < 0 {
+            return Err(StatusCode::UNEXPECTED_NULL);
+        }
+        self.clear();
+        if length == 0 {
+            return Ok(());
+        }
+
+        let magic: i32 = parcel.read()?;
+        if magic != BUNDLE_MAGIC && magic != BUNDLE_MAGIC_NATIVE {
+            return Err(StatusCode::BAD_VALUE);
+        }
+        self.read_entries(parcel)
+    }
+}
+
+impl ParcelableMetadata for PersistableBundle {
+    fn get_descriptor() -> &'static str {
+        "android.os.PersistableBundle"
+    }
+}
+
+crate::impl_serialize_for_parcelable!(PersistableBundle);
+crate::impl_deserialize_for_parcelable!(PersistableBundle);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parcel::Parcel;
+
+    fn sample() -> PersistableBundle {
+        let mut nested = PersistableBundle::new();
+        nested.insert_string_vec("names", vec!["a".into(), "b".into()]);
+
+        let mut bundle = PersistableBundle::new();
+        bundle.insert_bool("bool", true);
+        bundle.insert_int("int", -1);
+        bundle.insert_long("long", i64::MAX);
+        bundle.insert_double("double", 0.5);
+        bundle.insert_string("string", "text".to_string());
+        bundle.insert_bool_vec("bools", vec![true, false]);
+        bundle.insert_int_vec("ints", vec![1, 2, 3]);
+        bundle.insert_long_vec("longs", vec![]);
+        bundle.insert_double_vec("doubles", vec![1.5]);
+        bundle.insert_persistable_bundle("nested", nested);
+        bundle
+    }
+
+    #[test]
+    fn test_persistable_bundle_accessors() {
+        let mut bundle = sample();
+        assert_eq!(bundle.len(), 10);
+        assert_eq!(bundle.get_bool("bool"), Some(true));
+        assert_eq!(bundle.get_int("int"), Some(-1));
+        assert_eq!(bundle.get_string("string"), Some("text"));
+        assert_eq!(bundle.get_int_vec("ints"), Some(&[1, 2, 3][..]));
+        assert_eq!(
+            bundle.get_persistable_bundle("nested").and_then(|b| b.get_string_vec("names")),
+            Some(&["a".to_string(), "b".to_string()][..])
+        );
+
+        // Values of a different type are not returned, and are replaced by
+        // inserting a value with the same key.
+        assert_eq!(bundle.get_long("int"), None);
+        bundle.insert_long("int", 7);
+        assert_eq!(bundle.get_int("int"), None);
+        assert_eq!(bundle.get_long("int"), Some(7));
+        assert_eq!(bundle.len(), 10);
+
+        assert!(bundle.remove("int"));
+        assert!(!bundle.remove("int"));
+        assert!(!bundle.contains_key("int"));
+        assert_eq!(bundle.keys().next(), Some("bool"));
+    }
+
+    #[test]
+    fn test_persistable_bundle_parcel() {
+        let bundle = sample();
+        let mut parcel = Parcel::new();
+        parcel.write(&bundle).unwrap();
+        parcel.write(&PersistableBundle::new()).unwrap();
+        parcel.write(&None::<PersistableBundle>).unwrap();
+
+        parcel.rewind_to_start().unwrap();
+        assert_eq!(parcel.read(), Ok(bundle));
+        assert_eq!(parcel.read(), Ok(PersistableBundle::new()));
+        assert_eq!(parcel.read::<Option<PersistableBundle>>(), Ok(None));
+        assert_eq!(parcel.get_data_position(), parcel.get_data_size());
+    }
+
+    #[test]
+    fn test_persistable_bundle_wire_format() {
+        let mut bundle = PersistableBundle::new();
+        bundle.insert_int("a", 5);
+        let mut parcel = Parcel::new();
+        bundle.write_to_parcel(&mut parcel.borrowed()).unwrap();
+
+        parcel.rewind_to_start().unwrap();
+        assert_eq!(parcel.read::<i32>(), Ok(20));
+        assert_eq!(parcel.read::<i32>(), Ok(BUNDLE_MAGIC_NATIVE));
+        assert_eq!(parcel.read::<i32>(), Ok(1));
+        assert_eq!(parcel.read::<String>(), Ok("a".to_string()));
+        assert_eq!(parcel.read::<i32>(), Ok(VAL_INTEGER));
+        assert_eq!(parcel.read::<i32>(), Ok(5));
+
+        // Bundles written by Java use a different magic number, and unknown
+        // value types are rejected.
+        let mut parcel = Parcel::new();
+        parcel.write(&12i32).unwrap();
+        parcel.write(&BUNDLE_MAGIC).unwrap();
+        parcel.write(&1i32).unwrap();
+        parcel.write("x").unwrap();
+        parcel.write(&-1i32).unwrap();
+        parcel.rewind_to_start().unwrap();
+        let mut read = PersistableBundle::new();
+        assert_eq!(read.read_from_parcel(parcel.borrowed_ref()), Err(StatusCode::BAD_TYPE));
+    }
+}
@@ -0,0 +1,151 @@
+/*
+ * Copyright (C) 2022 The Android Open Source Project
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Helpers for testing that values survive being written to and read back
+//! from a parcel.
+//!
+//! With the `arbitrary` feature enabled, the parcelable types of this crate
+//! and enums declared with [`declare_binder_enum!`](crate::declare_binder_enum)
+//! implement [`arbitrary::Arbitrary`], so that round-trips can be tested on
+//! generated values with [`assert_arbitrary_round_trip`]. The Android build
+//! of `libbinder_rs` enables this feature.
+
+use crate::error::{Result, StatusCode};
+use crate::parcel::{Deserialize, Parcel, Serialize};
+
+use std::fmt;
+
+/// Write `value` to a new parcel and read it back.
+///
+/// Fails with `BAD_VALUE` if reading the value does not consume all the data
+/// that writing it produced.
+pub fn round_trip<T: Serialize + Deserialize>(value: &T) -> Result<T> {
+    let mut parcel = Parcel::new();
+    parcel.write(value)?;
+    parcel.rewind_to_start()?;
+    let read = parcel.read()?;
+    if parcel.get_data_position() != parcel.get_data_size() {
+        return Err(StatusCode::BAD_VALUE);
+    }
+    Ok(read)
+}
+
+/// Assert that `value` is unchanged by writing it to a parcel and reading it
+/// back.
+///
+/// The value that was read is compared with the original by writing it again
+/// and comparing the parcel contents, so this also works for types that do not
+/// implement `PartialEq` (like `ParcelableHolder`) and for NaN floats.
+///
+/// # Panics
+///
+/// Panics with a description of the differences if the round-trip fails or
+/// changes the value.
+pub fn assert_round_trip<T: Serialize + Deserialize + fmt::Debug>(value: &T) {
+    let read = match round_trip(value) {
+        Ok(read) => read,
+        Err(e) => panic!("Round-trip of {:?} failed: {:?}", value, e),
+    };
+
+    let mut expected = Parcel::new();
+    let mut actual = Parcel::new();
+    expected.write(value).expect("writing the original value failed");
+    actual.write(&read).expect("writing the value that was read failed");
+    if let Some(diff) = expected.diff(&actual) {
+        panic!("Round-trip changed {:?} into {:?}: {}", value, read, diff);
+    }
+}
+
+/// Generate a value of type `T` from `u` and assert that it round-trips, see
+/// [`assert_round_trip`].
+///
+/// This can be called directly from a fuzz target, or from a test looping
+/// over random data.
+#[cfg(feature = "arbitrary")]
+pub fn assert_arbitrary_round_trip<'a, T>(
+    u: &mut arbitrary::Unstructured<'a>,
+) -> arbitrary::Result<()>
+where
+    T: arbitrary::Arbitrary<'a> + Serialize + Deserialize + fmt::Debug,
+{
+    let value: T = u.arbitrary()?;
+    assert_round_trip(&value);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parcel::{BorrowedParcel, ParcelableHolder};
+
+    #[test]
+    fn test_round_trip() {
+        assert_eq!(round_trip(&42i32), Ok(42));
+        assert_eq!(round_trip(&Some("round".to_string())), Ok(Some("round".to_string())));
+        assert_eq!(round_trip(&vec![1u8, 2, 3]), Ok(vec![1, 2, 3]));
+
+        assert_round_trip(&f32::NAN);
+        assert_round_trip(&[Some("a".to_string()), None]);
+        assert_round_trip(&ParcelableHolder::new(Default::default()));
+    }
+
+    #[test]
+    #[should_panic(expected = "Round-trip changed")]
+    fn test_round_trip_mismatch() {
+        #[derive(Debug)]
+        struct Lossy(i32);
+
+        impl Serialize for Lossy {
+            fn serialize(&self, parcel: &mut BorrowedParcel<'_>) -> Result<()> {
+                parcel.write(&self.0)
+            }
+        }
+
+        impl Deserialize for Lossy {
+            fn deserialize(parcel: &BorrowedParcel<'_>) -> Result<Self> {
+                parcel.read::<i32>().map(|_| Lossy(0))
+            }
+        }
+
+        assert_round_trip(&Lossy(7));
+    }
+
+    #[cfg(feature = "arbitrary")]
+    #[test]
+    fn test_arbitrary_round_trip() {
+        crate::declare_binder_enum! {
+            Fruit : [i8; 2] {
+                APPLE = 0,
+                BANANA = 1,
+            }
+        }
+
+        let data: Vec<u8> =
+            (0..4096u32).map(|i| (i.wrapping_mul(2654435761) >> 13) as u8).collect();
+        let mut u = arbitrary::Unstructured::new(&data);
+        while !u.is_empty() {
+            assert_arbitrary_round_trip::<bool>(&mut u).unwrap();
+            assert_arbitrary_round_trip::<f64>(&mut u).unwrap();
+            assert_arbitrary_round_trip::<String>(&mut u).unwrap();
+            assert_arbitrary_round_trip::<Option<Vec<i32>>>(&mut u).unwrap();
+            assert_arbitrary_round_trip::<[u16; 3]>(&mut u).unwrap();
+            assert_arbitrary_round_trip::<Vec<Option<String>>>(&mut u).unwrap();
+            assert_arbitrary_round_trip::<Fruit>(&mut u).unwrap();
+            assert_arbitrary_round_trip::<Vec<Fruit>>(&mut u).unwrap();
+            assert_arbitrary_round_trip::<ParcelableHolder>(&mut u).unwrap();
+        }
+    }
+}
@@ -0,0 +1,465 @@
+/*
+ * Copyright (C) 2022 The Android Open Source Project
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Shared memory regions that can be sent through a parcel as a file
+//! descriptor.
+
+use super::{
+    BorrowedParcel, Deserialize, DeserializeArray, DeserializeOption, ParcelFileDescriptor,
+    Serialize, SerializeArray, SerializeOption,
+};
+use crate::error::{status_result, Result, StatusCode};
+
+use std::convert::TryInto;
+use std::ffi::CString;
+use std::fs::File;
+use std::io;
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::ptr::{self, NonNull};
+use std::slice;
+
+// Not defined by all versions of the libc crate. Added in Linux 5.1.
+const F_SEAL_FUTURE_WRITE: libc::c_int = 0x0010;
+
+/// Convert an OS error to the corresponding status code, as libbinder does
+/// for negated errno values.
+fn os_error(error: io::Error) -> StatusCode {
+    let errno = error.raw_os_error().unwrap_or(libc::EIO);
+    status_result(-errno).err().unwrap_or(StatusCode::UNKNOWN_ERROR)
+}
+
+fn last_os_error() -> StatusCode {
+    os_error(io::Error::last_os_error())
+}
+
+/// A region of shared memory, backed by a sealed `memfd`.
+///
+/// This is meant for buffers that are too large to be sent in a transaction.
+/// Only the file descriptor is written to the parcel, so the receiving process
+/// maps the same memory without any copy.
+///
+/// The size of the region is sealed when it is created, so that a peer cannot
+/// shrink it while it is mapped. Deserializing fails with `BAD_VALUE` if the
+/// received file descriptor is not a memfd with a sealed size. In particular,
+/// this is not compatible with the ashmem regions of the Java class
+/// android.os.SharedMemory.
+///
+/// Other processes may write to the region at any time, so mapped views don't
+/// hand out references to it. Instead, data is copied in and out with
+/// [`SharedMemoryView::read_at`] and [`SharedMemoryViewMut::write_at`].
+#[derive(Debug, PartialEq, Eq)]
+pub struct SharedMemory {
+    fd: ParcelFileDescriptor,
+    size: usize,
+}
+
+impl SharedMemory {
+    /// Create a new zero-filled region of `size` bytes.
+    ///
+    /// The name is only used for debugging, and shows up in
+    /// `/proc/<pid>/maps` of processes that map the region.
+    pub fn create(name: &str, size: usize) -> Result<Self> {
+        if size == 0 {
+            return Err(StatusCode::BAD_VALUE);
+        }
+        let len: libc::off_t = size.try_into().or(Err(StatusCode::BAD_VALUE))?;
+        let name = CString::new(name).or(Err(StatusCode::BAD_VALUE))?;
+
+        let fd = unsafe {
+            // Safety: `name` is a valid nul-terminated string, which is only
+            // read during the call.
+            libc::memfd_create(name.as_ptr(), libc::MFD_CLOEXEC | libc::MFD_ALLOW_SEALING)
+        };
+        if fd < 0 {
+            return Err(last_os_error());
+        }
+        let file = unsafe {
+            // Safety: `memfd_create` returned a new file descriptor, which
+            // nothing else owns.
+            File::from_raw_fd(fd)
+        };
+
+        if unsafe { libc::ftruncate(file.as_raw_fd(), len) } < 0 {
+            return Err(last_os_error());
+        }
+        add_seals(&file, libc::F_SEAL_SHRINK | libc::F_SEAL_GROW)?;
+
+        Ok(Self { fd: ParcelFileDescriptor::new(file), size })
+    }
+
+    /// Wrap an existing memfd, checking that its size is sealed.
+    fn from_fd(fd: ParcelFileDescriptor) -> Result<Self> {
+        let seals = get_seals(fd.as_ref()).or(Err(StatusCode::BAD_VALUE))?;
+        if seals & libc::F_SEAL_SHRINK == 0 {
+            return Err(StatusCode::BAD_VALUE);
+        }
+        let size = fd.as_ref().metadata().map_err(os_error)?.len();
+        let size: usize = size.try_into().or(Err(StatusCode::BAD_VALUE))?;
+        if size == 0 {
+            return Err(StatusCode::BAD_VALUE);
+        }
+        Ok(Self { fd, size })
+    }
+
+    /// Returns the size of the region in bytes.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Prevent the region from being mapped for writing from now on, by this
+    /// process or any process it is sent to.
+    ///
+    /// Views that are already mapped for writing stay writable, so a producer
+    /// can fill the region, seal it, and then share it with consumers that are
+    /// guaranteed not to modify it. This cannot be undone.
+    pub fn set_read_only(&self) -> Result<()> {
+        match add_seals(self.fd.as_ref(), F_SEAL_FUTURE_WRITE) {
+            // Kernels before 5.1 don't support F_SEAL_FUTURE_WRITE. F_SEAL_WRITE
+            // has the same effect, but fails while writable views exist.
+            Err(StatusCode::BAD_VALUE) => add_seals(self.fd.as_ref(), libc::F_SEAL_WRITE),
+            result => result,
+        }
+    }
+
+    /// Returns `true` if the region can no longer be mapped for writing.
+    pub fn is_read_only(&self) -> Result<bool> {
+        Ok(get_seals(self.fd.as_ref())? & (libc::F_SEAL_WRITE | F_SEAL_FUTURE_WRITE) != 0)
+    }
+
+    /// Map the whole region for reading.
+    ///
+    /// The contents may still change while the view is mapped if another
+    /// process has a writable view, unless the region was made read-only
+    /// before it was shared.
+    pub fn map_read_only(&self) -> Result<SharedMemoryView> {
+        Mapping::new(self, libc::PROT_READ).map(SharedMemoryView)
+    }
+
+    /// Map the whole region for reading and writing.
+    ///
+    /// Fails with `PERMISSION_DENIED` if the region is read-only.
+    pub fn map_read_write(&self) -> Result<SharedMemoryViewMut> {
+        Mapping::new(self, libc::PROT_READ | libc::PROT_WRITE).map(SharedMemoryViewMut)
+    }
+}
+
+fn get_seals(file: &File) -> Result<libc::c_int> {
+    let seals = unsafe {
+        // Safety: F_GET_SEALS takes no argument and only returns a value.
+        libc::fcntl(file.as_raw_fd(), libc::F_GET_SEALS)
+    };
+    if seals < 0 {
+        Err(last_os_error())
+    } else {
+        Ok(seals)
+    }
+}
+
+fn add_seals(file: &File, seals: libc::c_int) -> Result<()> {
+    let result = unsafe {
+        // Safety: F_ADD_SEALS takes an integer argument and has no effect on
+        // memory.
+        libc::fcntl(file.as_raw_fd(), libc::F_ADD_SEALS, seals)
+    };
+    if result < 0 {
+        Err(last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+impl AsRawFd for SharedMemory {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+/// A memory mapping of a whole `SharedMemory` region, unmapped when dropped.
+#[derive(Debug)]
+struct Mapping {
+    ptr: NonNull<u8>,
+    len: usize,
+}
+
+impl Mapping {
+    fn new(memory: &SharedMemory, prot: libc::c_int) -> Result<Self> {
+        let ptr = unsafe {
+            // Safety: We let the kernel choose the address of a new mapping,
+            // so this can't affect any existing memory. The size is non-zero
+            // and sealed, so the mapping is always backed by the file.
+            libc::mmap(
+                ptr::null_mut(),
+                memory.size,
+                prot,
+                libc::MAP_SHARED,
+                memory.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(last_os_error());
+        }
+        let ptr = NonNull::new(ptr as *mut u8).ok_or(StatusCode::UNKNOWN_ERROR)?;
+        Ok(Self { ptr, len: memory.size })
+    }
+
+    /// Check that `len` bytes at `offset` are within the mapping.
+    fn check_range(&self, offset: usize, len: usize) -> Result<()> {
+        match offset.checked_add(len) {
+            Some(end) if end <= self.len => Ok(()),
+            _ => Err(StatusCode::BAD_VALUE),
+        }
+    }
+
+    fn read_at(&self, offset: usize, buffer: &mut [u8]) -> Result<()> {
+        self.check_range(offset, buffer.len())?;
+        unsafe {
+            // Safety: The range was checked to be within the mapping, which
+            // is valid until it is dropped, and can't overlap `buffer`
+            // because no references into the mapping are handed out.
+            ptr::copy_nonoverlapping(
+                self.ptr.as_ptr().add(offset),
+                buffer.as_mut_ptr(),
+                buffer.len(),
+            );
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        unsafe {
+            // Safety: The mapping was created by `mmap` with this address and
+            // length, and no references into it outlive `self`.
+            libc::munmap(self.ptr.as_ptr() as *mut libc::c_void, self.len);
+        }
+    }
+}
+
+// Safety: The mapping is not tied to the thread that created it. It is only
+// accessed by copying through raw pointers, or through the unsafe slice
+// accessors of the views, whose callers must rule out concurrent writes.
+unsafe impl Send for Mapping {}
+unsafe impl Sync for Mapping {}
+
+/// A read-only view of a [`SharedMemory`] region.
+///
+/// The view stays valid after the `SharedMemory` it was created from is
+/// dropped.
+#[derive(Debug)]
+pub struct SharedMemoryView(Mapping);
+
+impl SharedMemoryView {
+    /// Returns the size of the view in bytes.
+    pub fn len(&self) -> usize {
+        self.0.len
+    }
+
+    /// Returns `true` if the view is empty. Views of a region are never
+    /// empty, since regions can't be created with a size of zero.
+    pub fn is_empty(&self) -> bool {
+        self.0.len == 0
+    }
+
+    /// Returns a pointer to the start of the mapped memory, which is valid
+    /// for reads of [`len`](Self::len) bytes while the view exists.
+    pub fn as_ptr(&self) -> *const u8 {
+        self.0.ptr.as_ptr()
+    }
+
+    /// Copy `buffer.len()` bytes starting at `offset` into `buffer`.
+    ///
+    /// Fails with `BAD_VALUE` if the range is out of bounds.
+    pub fn read_at(&self, offset: usize, buffer: &mut [u8]) -> Result<()> {
+        self.0.read_at(offset, buffer)
+    }
+
+    /// Returns the mapped memory as a slice, without copying it.
+    ///
+    /// # Safety
+    ///
+    /// No view of the region in this or any other process may write to it
+    /// while the returned slice exists. This holds if the region was made
+    /// read-only with [`SharedMemory::set_read_only`] before any writable
+    /// view was mapped or the region was shared.
+    pub unsafe fn as_slice(&self) -> &[u8] {
+        slice::from_raw_parts(self.0.ptr.as_ptr(), self.0.len)
+    }
+}
+
+/// A writable view of a [`SharedMemory`] region.
+///
+/// The view stays valid after the `SharedMemory` it was created from is
+/// dropped.
+#[derive(Debug)]
+pub struct SharedMemoryViewMut(Mapping);
+
+impl SharedMemoryViewMut {
+    /// Returns the size of the view in bytes.
+    pub fn len(&self) -> usize {
+        self.0.len
+    }
+
+    /// Returns `true` if the view is empty. Views of a region are never
+    /// empty, since regions can't be created with a size of zero.
+    pub fn is_empty(&self) -> bool {
+        self.0.len == 0
+    }
+
+    /// Returns a pointer to the start of the mapped memory, which is valid
+    /// for reads and writes of [`len`](Self::len) bytes while the view
+    /// exists.
+    pub fn as_mut_ptr(&mut self) -> *mut u8 {
+        self.0.ptr.as_ptr()
+    }
+
+    /// Copy `buffer.len()` bytes starting at `offset` into `buffer`.
+    ///
+    /// Fails with `BAD_VALUE` if the range is out of bounds.
+    pub fn read_at(&self, offset: usize, buffer: &mut [u8]) -> Result<()> {
+        self.0.read_at(offset, buffer)
+    }
+
+    /// Copy `data` into the region, starting at `offset`.
+    ///
+    /// Fails with `BAD_VALUE` if the range is out of bounds.
+    pub fn write_at(&mut self, offset: usize, data: &[u8]) -> Result<()> {
+        self.0.check_range(offset, data.len())?;
+        unsafe {
+            // Safety: The range was checked to be within the mapping, which
+            // is writable and valid until it is dropped, and can't overlap
+            // `data` because no references into the mapping are handed out
+            // while `self` is mutably borrowed.
+            ptr::copy_nonoverlapping(data.as_ptr(), self.0.ptr.as_ptr().add(offset), data.len());
+        }
+        Ok(())
+    }
+
+    /// Returns the mapped memory as a mutable slice, without copying it.
+    ///
+    /// # Safety
+    ///
+    /// No other view of the region in this or any other process may access
+    /// it while the returned slice exists.
+    pub unsafe fn as_mut_slice(&mut self) -> &mut [u8] {
+        slice::from_raw_parts_mut(self.0.ptr.as_ptr(), self.0.len)
+    }
+}
+
+impl Serialize for SharedMemory {
+    fn serialize(&self, parcel: &mut BorrowedParcel<'_>) -> Result<()> {
+        parcel.write(&self.fd)
+    }
+}
+
+impl SerializeArray for SharedMemory {}
+
+impl SerializeOption for SharedMemory {
+    fn serialize_option(this: Option<&Self>, parcel: &mut BorrowedParcel<'_>) -> Result<()> {
+        SerializeOption::serialize_option(this.map(|memory| &memory.fd), parcel)
+    }
+}
+
+impl DeserializeOption for SharedMemory {
+    fn deserialize_option(parcel: &BorrowedParcel<'_>) -> Result<Option<Self>> {
+        let fd: Option<ParcelFileDescriptor> = parcel.read()?;
+        fd.map(SharedMemory::from_fd).transpose()
+    }
+}
+
+impl Deserialize for SharedMemory {
+    fn deserialize(parcel: &BorrowedParcel<'_>) -> Result<Self> {
+        Deserialize::deserialize(parcel)
+            .transpose()
+            .unwrap_or(Err(StatusCode::UNEXPECTED_NULL))
+    }
+}
+
+impl DeserializeArray for SharedMemory {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parcel::Parcel;
+
+    #[test]
+    fn test_shared_memory_views() {
+        assert_eq!(SharedMemory::create("empty", 0).err(), Some(StatusCode::BAD_VALUE));
+
+        let memory = SharedMemory::create("test", 3 << 20).unwrap();
+        assert_eq!(memory.size(), 3 << 20);
+        assert_eq!(memory.is_read_only(), Ok(false));
+
+        let mut writer = memory.map_read_write().unwrap();
+        let reader = memory.map_read_only().unwrap();
+        assert_eq!(reader.len(), 3 << 20);
+        let mut contents = vec![0xaa; reader.len()];
+        reader.read_at(0, &mut contents).unwrap();
+        assert!(contents.iter().all(|&b| b == 0));
+
+        writer.write_at(0, b"hello").unwrap();
+        writer.write_at((3 << 20) - 1, &[0xff]).unwrap();
+        let mut buffer = [0; 5];
+        reader.read_at(0, &mut buffer).unwrap();
+        assert_eq!(&buffer, b"hello");
+        reader.read_at((3 << 20) - 1, &mut buffer[..1]).unwrap();
+        assert_eq!(buffer[0], 0xff);
+
+        assert_eq!(writer.write_at(3 << 20, &[1]), Err(StatusCode::BAD_VALUE));
+        assert_eq!(reader.read_at((3 << 20) - 1, &mut buffer), Err(StatusCode::BAD_VALUE));
+        assert_eq!(reader.read_at(usize::MAX, &mut buffer), Err(StatusCode::BAD_VALUE));
+
+        // Views outlive the region they were mapped from.
+        drop(memory);
+        drop(reader);
+        // Safety: No other view of the region exists any more.
+        assert_eq!(&unsafe { writer.as_mut_slice() }[..5], b"hello");
+    }
+
+    #[test]
+    fn test_shared_memory_read_only() {
+        let memory = SharedMemory::create("test", 4096).unwrap();
+        memory.map_read_write().unwrap().write_at(0, &[1]).unwrap();
+
+        memory.set_read_only().unwrap();
+        assert_eq!(memory.is_read_only(), Ok(true));
+        assert_eq!(memory.map_read_write().err(), Some(StatusCode::PERMISSION_DENIED));
+        let reader = memory.map_read_only().unwrap();
+        // Safety: The region is read-only, and no writable view exists.
+        assert_eq!(unsafe { reader.as_slice() }[0], 1);
+    }
+
+    #[test]
+    fn test_shared_memory_parcel() {
+        let memory = SharedMemory::create("test", 8192).unwrap();
+        memory.map_read_write().unwrap().write_at(8191, &[42]).unwrap();
+
+        let mut parcel = Parcel::new();
+        parcel.write(&memory).unwrap();
+        parcel.write(&None::<SharedMemory>).unwrap();
+        parcel.write(&ParcelFileDescriptor::new(File::open("/dev/null").unwrap())).unwrap();
+
+        parcel.rewind_to_start().unwrap();
+        let received: SharedMemory = parcel.read().unwrap();
+        assert_eq!(received.size(), 8192);
+        let mut byte = [0];
+        received.map_read_only().unwrap().read_at(8191, &mut byte).unwrap();
+        assert_eq!(byte, [42]);
+        assert_eq!(parcel.read::<Option<SharedMemory>>(), Ok(None));
+        assert_eq!(parcel.read::<SharedMemory>().err(), Some(StatusCode::BAD_VALUE));
+    }
+}
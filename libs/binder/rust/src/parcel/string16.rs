@@ -0,0 +1,285 @@
+/*
+ * Copyright (C) 2022 The Android Open Source Project
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use super::{
+    limits, BorrowedParcel, Deserialize, DeserializeArray, DeserializeOption, Serialize,
+    SerializeArray, SerializeOption,
+};
+use crate::error::{Result, StatusCode};
+
+use std::char;
+use std::convert::{TryFrom, TryInto};
+use std::fmt;
+use std::string::FromUtf16Error;
+
+/// An owned string of UTF-16 code units, like `android::String16` in C++.
+///
+/// Strings in a parcel are encoded in UTF-16, so `String` has to be transcoded
+/// from and to UTF-8 whenever it is read or written. A `String16` holds the
+/// code units as they appear in the parcel instead, which avoids that cost for
+/// strings that are only passed through, and preserves strings that are not
+/// valid UTF-16 (e.g. with unpaired surrogates, which Java allows).
+///
+/// The wire format is the same as for `String`, so the two can be used
+/// interchangeably on either side of a transaction.
+///
+/// The read limit on string lengths (see
+/// [`ReadLimits::max_string_bytes`](crate::ReadLimits::max_string_bytes))
+/// counts UTF-16 code units for a `String16`, since it is not decoded.
+#[derive(Default, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct String16(Vec<u16>);
+
+impl String16 {
+    /// Create a new, empty `String16`.
+    pub fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    /// Create a `String16` from UTF-16 code units, which don't need to be
+    /// valid UTF-16.
+    pub fn from_units(units: Vec<u16>) -> Self {
+        Self(units)
+    }
+
+    /// Returns the UTF-16 code units of this string.
+    pub fn as_units(&self) -> &[u16] {
+        &self.0
+    }
+
+    /// Returns the UTF-16 code units of this string, consuming it.
+    pub fn into_units(self) -> Vec<u16> {
+        self.0
+    }
+
+    /// Returns the length of this string, in UTF-16 code units.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns `true` if this string has no code units.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Convert this string to UTF-8, replacing invalid code units with
+    /// `U+FFFD REPLACEMENT CHARACTER`.
+    pub fn to_string_lossy(&self) -> String {
+        String::from_utf16_lossy(&self.0)
+    }
+}
+
+impl From<&str> for String16 {
+    fn from(s: &str) -> Self {
+        Self(s.encode_utf16().collect())
+    }
+}
+
+impl From<String> for String16 {
+    fn from(s: String) -> Self {
+        s.as_str().into()
+    }
+}
+
+impl From<Vec<u16>> for String16 {
+    fn from(units: Vec<u16>) -> Self {
+        Self(units)
+    }
+}
+
+impl TryFrom<&String16> for String {
+    type Error = FromUtf16Error;
+
+    fn try_from(s: &String16) -> std::result::Result<String, FromUtf16Error> {
+        String::from_utf16(&s.0)
+    }
+}
+
+impl TryFrom<String16> for String {
+    type Error = FromUtf16Error;
+
+    fn try_from(s: String16) -> std::result::Result<String, FromUtf16Error> {
+        String::try_from(&s)
+    }
+}
+
+impl fmt::Display for String16 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for c in char::decode_utf16(self.0.iter().copied()) {
+            fmt::Write::write_char(f, c.unwrap_or(char::REPLACEMENT_CHARACTER))?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for String16 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("\"")?;
+        for c in char::decode_utf16(self.0.iter().copied()) {
+            match c {
+                Ok(c) => write!(f, "{}", c.escape_debug())?,
+                Err(e) => write!(f, "\\u{{{:x}}}", e.unpaired_surrogate())?,
+            }
+        }
+        f.write_str("\"")
+    }
+}
+
+impl Serialize for String16 {
+    fn serialize(&self, parcel: &mut BorrowedParcel<'_>) -> Result<()> {
+        SerializeOption::serialize_option(Some(self), parcel)
+    }
+}
+
+impl SerializeOption for String16 {
+    fn serialize_option(this: Option<&Self>, parcel: &mut BorrowedParcel<'_>) -> Result<()> {
+        let units = match this {
+            None => return parcel.write(&-1i32),
+            Some(s) => &s.0,
+        };
+        let len: i32 = units.len().try_into().or(Err(StatusCode::BAD_VALUE))?;
+        parcel.write(&len)?;
+
+        // The code units and the null terminator are written in pairs as
+        // 32-bit words, which gives the same layout and zero padding as
+        // `writeString16`.
+        let mut units = units.iter().copied().chain(Some(0));
+        while let Some(first) = units.next() {
+            let second = units.next().unwrap_or(0);
+            let [a, b] = first.to_ne_bytes();
+            let [c, d] = second.to_ne_bytes();
+            parcel.write(&u32::from_ne_bytes([a, b, c, d]))?;
+        }
+        Ok(())
+    }
+}
+
+impl SerializeArray for String16 {}
+
+impl Deserialize for String16 {
+    fn deserialize(parcel: &BorrowedParcel<'_>) -> Result<Self> {
+        Deserialize::deserialize(parcel)
+            .transpose()
+            .unwrap_or(Err(StatusCode::UNEXPECTED_NULL))
+    }
+}
+
+impl DeserializeOption for String16 {
+    fn deserialize_option(parcel: &BorrowedParcel<'_>) -> Result<Option<Self>> {
+        let len: i32 = parcel.read()?;
+        if len == -1 {
+            return Ok(None);
+        }
+        limits::check_string_len(len)?;
+        let len = usize::try_from(len).or(Err(StatusCode::BAD_VALUE))?;
+
+        // Check the length against the remaining data before allocating, since
+        // it comes from the sender.
+        let words = len / 2 + 1;
+        let remaining = parcel.get_data_size() - parcel.get_data_position();
+        if words > usize::try_from(remaining).unwrap_or(0) / 4 {
+            return Err(StatusCode::NOT_ENOUGH_DATA);
+        }
+
+        // The code units and the null terminator are read in pairs as 32-bit
+        // words, like they are written.
+        let mut units = Vec::with_capacity(words * 2);
+        for _ in 0..words {
+            let [a, b, c, d] = parcel.read::<u32>()?.to_ne_bytes();
+            units.push(u16::from_ne_bytes([a, b]));
+            units.push(u16::from_ne_bytes([c, d]));
+        }
+        // Drop the padding, if any, and the null terminator.
+        units.truncate(len + 1);
+        if units.pop() != Some(0) {
+            return Err(StatusCode::BAD_VALUE);
+        }
+        Ok(Some(Self(units)))
+    }
+}
+
+impl DeserializeArray for String16 {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parcel::Parcel;
+
+    #[test]
+    fn test_string16_wire_format() {
+        for &s in &["", "a", "abc", "\u{e9}t\u{e9}", "\u{1f600}"] {
+            let mut expected = Parcel::new();
+            expected.write(s).unwrap();
+            let mut actual = Parcel::new();
+            actual.write(&String16::from(s)).unwrap();
+            assert_eq!(actual.marshal(), expected.marshal(), "{:?}", s);
+
+            expected.rewind_to_start().unwrap();
+            assert_eq!(expected.read::<String16>(), Ok(String16::from(s)));
+        }
+
+        let mut parcel = Parcel::new();
+        parcel.write(&None::<String16>).unwrap();
+        parcel.write(&vec![String16::from("x"), String16::new()]).unwrap();
+        parcel.rewind_to_start().unwrap();
+        assert_eq!(parcel.read::<Option<String>>(), Ok(None));
+        assert_eq!(parcel.read::<Vec<String>>(), Ok(vec!["x".to_string(), String::new()]));
+    }
+
+    #[test]
+    fn test_string16_unpaired_surrogate() {
+        let s = String16::from_units(vec![0xd800, 0x61]);
+        assert_eq!(s.to_string_lossy(), "\u{fffd}a");
+        assert!(String::try_from(&s).is_err());
+        assert_eq!(format!("{:?}", s), "\"\\u{d800}a\"");
+
+        let mut parcel = Parcel::new();
+        parcel.write(&s).unwrap();
+        parcel.rewind_to_start().unwrap();
+        assert_eq!(parcel.read::<String16>(), Ok(s));
+    }
+
+    #[test]
+    fn test_string16_read_limits() {
+        use crate::ReadLimits;
+
+        // Three code units, but five bytes in UTF-8.
+        let s = String16::from("\u{e9}t\u{e9}");
+        let mut parcel = Parcel::new();
+        parcel.write(&s).unwrap();
+
+        let limits = ReadLimits { max_string_bytes: 2, ..ReadLimits::UNLIMITED };
+        parcel.rewind_to_start().unwrap();
+        assert_eq!(parcel.read_with_limits::<String16>(limits), Err(StatusCode::BAD_VALUE));
+
+        let limits = ReadLimits { max_string_bytes: 3, ..ReadLimits::UNLIMITED };
+        parcel.rewind_to_start().unwrap();
+        assert_eq!(parcel.read_with_limits::<String16>(limits), Ok(s));
+    }
+
+    #[test]
+    fn test_string16_invalid() {
+        let mut parcel = Parcel::new();
+        // Missing null terminator.
+        parcel.write(&1i32).unwrap();
+        parcel.write(&0x0061_0061u32).unwrap();
+        // Claims more data than the parcel holds.
+        parcel.write(&0x1000_0000i32).unwrap();
+
+        parcel.rewind_to_start().unwrap();
+        assert_eq!(parcel.read::<String16>(), Err(StatusCode::BAD_VALUE));
+        assert_eq!(parcel.read::<String16>(), Err(StatusCode::NOT_ENOUGH_DATA));
+    }
+}
@@ -0,0 +1,164 @@
+/*
+ * Copyright (C) 2022 The Android Open Source Project
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Parcel encodings for `std::time` types.
+//!
+//! Both types are written like the serialized forms of their `java.time`
+//! counterparts, so that Java and C++ peers can read them with two plain reads:
+//!
+//! * `Duration` as a `long` number of seconds followed by an `int` number of
+//!   nanoseconds, like `java.time.Duration`.
+//! * `SystemTime` as a `long` number of seconds since the Unix epoch followed
+//!   by an `int` number of nanoseconds, like `java.time.Instant`. Times before
+//!   the epoch have negative seconds, and the nanoseconds are always positive.
+
+use super::{BorrowedParcel, Deserialize, DeserializeArray, Serialize, SerializeArray};
+use crate::error::{Result, StatusCode};
+
+use std::convert::TryFrom;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const NANOS_PER_SEC: u32 = 1_000_000_000;
+
+fn write_seconds_and_nanos(
+    parcel: &mut BorrowedParcel<'_>,
+    seconds: i64,
+    nanos: u32,
+) -> Result<()> {
+    parcel.write(&seconds)?;
+    parcel.write(&(nanos as i32))
+}
+
+fn read_seconds_and_nanos(parcel: &BorrowedParcel<'_>) -> Result<(i64, u32)> {
+    let seconds: i64 = parcel.read()?;
+    let nanos: i32 = parcel.read()?;
+    match u32::try_from(nanos) {
+        Ok(nanos) if nanos < NANOS_PER_SEC => Ok((seconds, nanos)),
+        _ => Err(StatusCode::BAD_VALUE),
+    }
+}
+
+impl Serialize for Duration {
+    fn serialize(&self, parcel: &mut BorrowedParcel<'_>) -> Result<()> {
+        let seconds = i64::try_from(self.as_secs()).or(Err(StatusCode::BAD_VALUE))?;
+        write_seconds_and_nanos(parcel, seconds, self.subsec_nanos())
+    }
+}
+
+impl Deserialize for Duration {
+    fn deserialize(parcel: &BorrowedParcel<'_>) -> Result<Self> {
+        let (seconds, nanos) = read_seconds_and_nanos(parcel)?;
+        // Negative durations are valid in Java, but not in Rust.
+        let seconds = u64::try_from(seconds).or(Err(StatusCode::BAD_VALUE))?;
+        Ok(Duration::new(seconds, nanos))
+    }
+}
+
+impl SerializeArray for Duration {}
+
+impl DeserializeArray for Duration {}
+
+impl Serialize for SystemTime {
+    fn serialize(&self, parcel: &mut BorrowedParcel<'_>) -> Result<()> {
+        let (seconds, nanos) = match self.duration_since(UNIX_EPOCH) {
+            Ok(after) => (
+                i64::try_from(after.as_secs()).or(Err(StatusCode::BAD_VALUE))?,
+                after.subsec_nanos(),
+            ),
+            Err(e) => {
+                let before = e.duration();
+                let seconds = i64::try_from(before.as_secs()).or(Err(StatusCode::BAD_VALUE))?;
+                if before.subsec_nanos() == 0 {
+                    (-seconds, 0)
+                } else {
+                    (-seconds - 1, NANOS_PER_SEC - before.subsec_nanos())
+                }
+            }
+        };
+        write_seconds_and_nanos(parcel, seconds, nanos)
+    }
+}
+
+impl Deserialize for SystemTime {
+    fn deserialize(parcel: &BorrowedParcel<'_>) -> Result<Self> {
+        let (seconds, nanos) = read_seconds_and_nanos(parcel)?;
+        let whole_seconds = Duration::from_secs(seconds.unsigned_abs());
+        let time = if seconds >= 0 {
+            UNIX_EPOCH.checked_add(whole_seconds)
+        } else {
+            UNIX_EPOCH.checked_sub(whole_seconds)
+        };
+        time.and_then(|time| time.checked_add(Duration::from_nanos(nanos.into())))
+            .ok_or(StatusCode::BAD_VALUE)
+    }
+}
+
+impl SerializeArray for SystemTime {}
+
+impl DeserializeArray for SystemTime {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parcel::Parcel;
+
+    #[test]
+    fn test_duration() {
+        let mut parcel = Parcel::new();
+        let duration = Duration::new(5, 250_000_000);
+        parcel.write(&duration).unwrap();
+        parcel.write(&vec![Duration::ZERO, Duration::from_nanos(1)]).unwrap();
+
+        parcel.rewind_to_start().unwrap();
+        assert_eq!(parcel.read::<i64>(), Ok(5));
+        assert_eq!(parcel.read::<i32>(), Ok(250_000_000));
+
+        parcel.rewind_to_start().unwrap();
+        assert_eq!(parcel.read(), Ok(duration));
+        assert_eq!(parcel.read(), Ok(vec![Duration::ZERO, Duration::from_nanos(1)]));
+
+        let mut parcel = Parcel::new();
+        parcel.write(&-1i64).unwrap();
+        parcel.write(&0i32).unwrap();
+        parcel.write(&0i64).unwrap();
+        parcel.write(&(NANOS_PER_SEC as i32)).unwrap();
+        parcel.rewind_to_start().unwrap();
+        assert_eq!(parcel.read::<Duration>(), Err(StatusCode::BAD_VALUE));
+        assert_eq!(parcel.read::<Duration>(), Err(StatusCode::BAD_VALUE));
+    }
+
+    #[test]
+    fn test_system_time() {
+        let after = UNIX_EPOCH + Duration::new(1_600_000_000, 42);
+        let before = UNIX_EPOCH - Duration::from_millis(1500);
+
+        let mut parcel = Parcel::new();
+        parcel.write(&after).unwrap();
+        parcel.write(&before).unwrap();
+        parcel.write(&UNIX_EPOCH).unwrap();
+
+        parcel.rewind_to_start().unwrap();
+        assert_eq!(parcel.read::<i64>(), Ok(1_600_000_000));
+        assert_eq!(parcel.read::<i32>(), Ok(42));
+        assert_eq!(parcel.read::<i64>(), Ok(-2));
+        assert_eq!(parcel.read::<i32>(), Ok(500_000_000));
+
+        parcel.rewind_to_start().unwrap();
+        assert_eq!(parcel.read(), Ok(after));
+        assert_eq!(parcel.read(), Ok(before));
+        assert_eq!(parcel.read(), Ok(UNIX_EPOCH));
+    }
+}
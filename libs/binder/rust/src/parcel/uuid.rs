@@ -0,0 +1,178 @@
+/*
+ * Copyright (C) 2022 The Android Open Source Project
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use super::{BorrowedParcel, Parcelable, ParcelableMetadata};
+use crate::error::{Result, StatusCode};
+
+use std::fmt;
+use std::str::FromStr;
+
+/// Rust version of the Java class android.os.ParcelUuid
+///
+/// Like in Java, this is a parcelable holding the most significant and then
+/// the least significant 64 bits of the UUID, each written as a `long`.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ParcelUuid(u128);
+
+impl ParcelUuid {
+    /// Create a new `ParcelUuid` from its 128-bit value.
+    pub const fn from_u128(value: u128) -> Self {
+        Self(value)
+    }
+
+    /// Create a new `ParcelUuid` from its most and least significant bits,
+    /// like the `java.util.UUID` constructor.
+    pub const fn from_bits(most_significant: u64, least_significant: u64) -> Self {
+        Self((most_significant as u128) << 64 | least_significant as u128)
+    }
+
+    /// Create a new `ParcelUuid` from its bytes, in big-endian order.
+    pub const fn from_bytes(bytes: [u8; 16]) -> Self {
+        Self(u128::from_be_bytes(bytes))
+    }
+
+    /// Returns the 128-bit value of this UUID.
+    pub const fn as_u128(&self) -> u128 {
+        self.0
+    }
+
+    /// Returns the bytes of this UUID, in big-endian order.
+    pub const fn to_bytes(&self) -> [u8; 16] {
+        self.0.to_be_bytes()
+    }
+
+    /// Returns the most significant 64 bits of this UUID.
+    pub const fn most_significant_bits(&self) -> u64 {
+        (self.0 >> 64) as u64
+    }
+
+    /// Returns the least significant 64 bits of this UUID.
+    pub const fn least_significant_bits(&self) -> u64 {
+        self.0 as u64
+    }
+}
+
+impl From<u128> for ParcelUuid {
+    fn from(value: u128) -> Self {
+        Self(value)
+    }
+}
+
+impl From<ParcelUuid> for u128 {
+    fn from(uuid: ParcelUuid) -> u128 {
+        uuid.0
+    }
+}
+
+/// Formats the UUID in the standard hyphenated form, like
+/// `java.util.UUID.toString`.
+impl fmt::Display for ParcelUuid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let v = self.0;
+        write!(
+            f,
+            "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+            v >> 96,
+            (v >> 80) & 0xffff,
+            (v >> 64) & 0xffff,
+            (v >> 48) & 0xffff,
+            v & 0xffff_ffff_ffff
+        )
+    }
+}
+
+/// Parses a UUID in the standard hyphenated form, like
+/// `java.util.UUID.fromString` but with exactly 32 hex digits.
+impl FromStr for ParcelUuid {
+    type Err = StatusCode;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let groups: Vec<&str> = s.split('-').collect();
+        let lengths = [8, 4, 4, 4, 12];
+        if groups.len() != lengths.len()
+            || groups.iter().zip(lengths.iter()).any(|(group, &len)| {
+                group.len() != len || !group.bytes().all(|b| b.is_ascii_hexdigit())
+            })
+        {
+            return Err(StatusCode::BAD_VALUE);
+        }
+        u128::from_str_radix(&groups.concat(), 16).map(Self).or(Err(StatusCode::BAD_VALUE))
+    }
+}
+
+impl Parcelable for ParcelUuid {
+    fn write_to_parcel(&self, parcel: &mut BorrowedParcel<'_>) -> Result<()> {
+        parcel.write(&(self.most_significant_bits() as i64))?;
+        parcel.write(&(self.least_significant_bits() as i64))
+    }
+
+    fn read_from_parcel(&mut self, parcel: &BorrowedParcel<'_>) -> Result<()> {
+        let most_significant: i64 = parcel.read()?;
+        let least_significant: i64 = parcel.read()?;
+        *self = Self::from_bits(most_significant as u64, least_significant as u64);
+        Ok(())
+    }
+}
+
+impl ParcelableMetadata for ParcelUuid {
+    fn get_descriptor() -> &'static str {
+        "android.os.ParcelUuid"
+    }
+}
+
+crate::impl_serialize_for_parcelable!(ParcelUuid);
+crate::impl_deserialize_for_parcelable!(ParcelUuid);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parcel::{Parcel, NON_NULL_PARCELABLE_FLAG};
+
+    const UUID: ParcelUuid = ParcelUuid::from_u128(0x00112233_4455_6677_8899_aabbccddeeff);
+
+    #[test]
+    fn test_uuid_parcel() {
+        let mut parcel = Parcel::new();
+        parcel.write(&UUID).unwrap();
+        parcel.write(&None::<ParcelUuid>).unwrap();
+
+        parcel.rewind_to_start().unwrap();
+        assert_eq!(parcel.read::<i32>(), Ok(NON_NULL_PARCELABLE_FLAG));
+        assert_eq!(parcel.read::<i64>(), Ok(0x0011223344556677));
+        assert_eq!(parcel.read::<i64>(), Ok(0x8899aabbccddeeffu64 as i64));
+
+        parcel.rewind_to_start().unwrap();
+        assert_eq!(parcel.read(), Ok(UUID));
+        assert_eq!(parcel.read::<Option<ParcelUuid>>(), Ok(None));
+    }
+
+    #[test]
+    fn test_uuid_string() {
+        assert_eq!(UUID.to_string(), "00112233-4455-6677-8899-aabbccddeeff");
+        assert_eq!("00112233-4455-6677-8899-AABBCCDDEEFF".parse(), Ok(UUID));
+        assert_eq!(
+            "00112233-4455-6677-8899aabbccddeeff".parse::<ParcelUuid>(),
+            Err(StatusCode::BAD_VALUE)
+        );
+        assert_eq!(
+            "00112233-4455-6677-8899-+abbccddeeff".parse::<ParcelUuid>(),
+            Err(StatusCode::BAD_VALUE)
+        );
+        assert_eq!(ParcelUuid::from_bytes(UUID.to_bytes()), UUID);
+        assert_eq!(UUID.most_significant_bits(), 0x0011223344556677);
+        assert_eq!(UUID.least_significant_bits(), 0x8899aabbccddeeff);
+    }
+}
@@ -22,9 +22,10 @@ use crate::binder::{
 };
 use crate::error::{status_result, Result, StatusCode};
 use crate::parcel::{
-    Parcel, BorrowedParcel, Deserialize, DeserializeArray, DeserializeOption, Serialize, SerializeArray, SerializeOption,
+    limits, Parcel, BorrowedParcel, Deserialize, DeserializeArray, DeserializeOption, Serialize, SerializeArray, SerializeOption,
 };
 use crate::sys;
+use crate::weak::{self, weak_status_result};
 
 use std::cmp::Ordering;
 use std::convert::TryInto;
@@ -265,6 +266,9 @@ impl<T: AsNative<sys::AIBinder>> IBinderInternal for T {
         data: Parcel,
         flags: TransactionFlags,
     ) -> Result<Parcel> {
+        // The read limits of a transaction this thread is handling don't apply
+        // to the reply.
+        limits::clear_read_limits();
         let mut reply = ptr::null_mut();
         let status = unsafe {
             // Safety: `SpIBinder` guarantees that `self` always contains a
@@ -303,6 +307,48 @@ impl<T: AsNative<sys::AIBinder>> IBinderInternal for T {
         }
     }
 
+    fn reset_transact(&self, data: &mut Parcel) -> Result<()> {
+        let status = unsafe {
+            // Safety: `SpIBinder` guarantees that `self` always contains a
+            // valid pointer to an `AIBinder`, and `Parcel` always contains a
+            // valid pointer to an `AParcel`. As for `prepare_transact`, it is
+            // safe to cast our immutable binder pointer to mutable. This call
+            // does not take ownership of either parameter.
+            weak::AIBinder_resetTransaction(
+                self.as_native() as *mut sys::AIBinder,
+                data.as_native_mut(),
+            )
+        };
+        weak_status_result(status)
+    }
+
+    fn submit_transact_into(
+        &self,
+        code: TransactionCode,
+        data: &Parcel,
+        reply: &mut Parcel,
+        flags: TransactionFlags,
+    ) -> Result<()> {
+        // As in `submit_transact`, the reply is read without limits.
+        limits::clear_read_limits();
+        let status = unsafe {
+            // Safety: `SpIBinder` guarantees that `self` always contains a
+            // valid pointer to an `AIBinder`, and `Parcel` always contains a
+            // valid pointer to an `AParcel`. Casting our immutable binder
+            // pointer to mutable is safe for the same reasons as in
+            // `submit_transact`. This call does not take ownership of any of
+            // its parameters.
+            weak::AIBinder_transactInto(
+                self.as_native() as *mut sys::AIBinder,
+                code,
+                data.as_native(),
+                reply.as_native_mut(),
+                flags,
+            )
+        };
+        weak_status_result(status)
+    }
+
     fn is_binder_alive(&self) -> bool {
         unsafe {
             // Safety: `SpIBinder` guarantees that `self` always contains a
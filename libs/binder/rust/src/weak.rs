@@ -0,0 +1,128 @@
+/*
+ * Copyright (C) 2022 The Android Open Source Project
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Runtime lookup of libbinder_ndk functions that are not part of its stable
+//! API.
+//!
+//! The APEX and vendor variants of this crate link against the libbinder_ndk
+//! stubs, which only have the stable symbols, so calling platform-only
+//! functions directly would fail to link. Instead, these functions are looked
+//! up when first called, and are unavailable on devices that don't have them.
+
+use crate::error::{status_result, status_t, Result, StatusCode};
+use crate::sys;
+
+use std::ffi::c_void;
+use std::os::raw::c_char;
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Address stored by a `WeakSymbol` that has not been looked up yet. This can
+/// never be the address of a function.
+const UNRESOLVED: usize = 1;
+
+/// A function symbol that is looked up in the loaded libraries on first use.
+pub(crate) struct WeakSymbol {
+    /// Nul-terminated name of the symbol.
+    name: &'static str,
+    address: AtomicUsize,
+}
+
+impl WeakSymbol {
+    /// Create a new symbol, which must be nul-terminated.
+    pub(crate) const fn new(name: &'static str) -> Self {
+        Self { name, address: AtomicUsize::new(UNRESOLVED) }
+    }
+
+    /// Returns the address of the symbol, or `None` if it is not available.
+    pub(crate) fn get(&self) -> Option<NonNull<c_void>> {
+        let address = match self.address.load(Ordering::Acquire) {
+            UNRESOLVED => {
+                assert!(self.name.ends_with('\0'), "Symbol name must be nul-terminated");
+                let address = unsafe {
+                    // Safety: `name` is a valid nul-terminated string.
+                    libc::dlsym(libc::RTLD_DEFAULT, self.name.as_ptr() as *const c_char)
+                } as usize;
+                self.address.store(address, Ordering::Release);
+                address
+            }
+            address => address,
+        };
+        NonNull::new(address as *mut c_void)
+    }
+}
+
+/// Declare wrappers for libbinder_ndk functions looked up at runtime.
+///
+/// Each wrapper has the signature of the NDK function, but returns `None`
+/// instead of calling it if the function is not available.
+macro_rules! weak_ndk_functions {
+    {
+        $(
+            fn $name:ident($($arg:ident: $arg_ty:ty),* $(,)?) -> $ret:ty;
+        )*
+    } => {
+        $(
+            #[allow(non_snake_case)]
+            pub(crate) unsafe fn $name($($arg: $arg_ty),*) -> Option<$ret> {
+                static SYMBOL: WeakSymbol = WeakSymbol::new(concat!(stringify!($name), "\0"));
+                let address = SYMBOL.get()?;
+                // Safety: The symbol is the libbinder_ndk function with this
+                // signature, and the caller upholds its safety requirements.
+                let function: unsafe extern "C" fn($($arg_ty),*) -> $ret =
+                    std::mem::transmute(address.as_ptr());
+                Some(function($($arg),*))
+            }
+        )*
+    };
+}
+
+// Platform-only functions, added in Android 14 (API level 34).
+weak_ndk_functions! {
+    fn AParcel_setDataCapacity(parcel: *mut sys::AParcel, capacity: usize) -> status_t;
+    fn AParcel_writeRawBytes(parcel: *mut sys::AParcel, buffer: *const u8, len: usize) -> status_t;
+    fn AParcel_readRawBytes(parcel: *const sys::AParcel, buffer: *mut u8, len: usize) -> status_t;
+    fn AIBinder_resetTransaction(binder: *mut sys::AIBinder, parcel: *mut sys::AParcel) -> status_t;
+    fn AIBinder_transactInto(
+        binder: *mut sys::AIBinder,
+        code: u32,
+        data: *const sys::AParcel,
+        reply: *mut sys::AParcel,
+        flags: u32,
+    ) -> status_t;
+}
+
+/// Convert the status returned by a weak NDK function to a `Result`, failing
+/// with `INVALID_OPERATION` if the function was not available.
+pub(crate) fn weak_status_result(status: Option<status_t>) -> Result<()> {
+    status.map_or(Err(StatusCode::INVALID_OPERATION), status_result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_weak_symbol() {
+        static PRESENT: WeakSymbol = WeakSymbol::new("AParcel_create\0");
+        static MISSING: WeakSymbol = WeakSymbol::new("AParcel_doesNotExist\0");
+
+        let address = PRESENT.get().expect("AParcel_create should be available");
+        assert_eq!(PRESENT.get(), Some(address));
+        assert_eq!(MISSING.get(), None);
+        assert_eq!(MISSING.get(), None);
+    }
+}
@@ -0,0 +1,266 @@
+/*
+ * Copyright (C) 2022 The Android Open Source Project
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Checks the wire format of the Rust `Serialize` implementations against a
+//! corpus of golden parcel layouts, using in-memory parcels only.
+//!
+//! When a layout changes on purpose, the test prints the new corpus lines for
+//! the cases that failed, which can be pasted into `golden/wire_format.txt`.
+
+use binder::binder_impl::{BorrowedParcel, Deserialize, Parcel, ParcelableMetadata, Serialize};
+use binder::{
+    declare_binder_enum, declare_binder_union, impl_deserialize_for_parcelable,
+    impl_serialize_for_parcelable, ExceptionCode, ParcelUuid, Parcelable, ParcelableHolder,
+    PersistableBundle, Status, StatusCode, String16,
+};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::ffi::CString;
+use std::fmt::Write;
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
+
+const CORPUS: &str = include_str!("golden/wire_format.txt");
+
+declare_binder_enum! {
+    Fruit : [i8; 2] {
+        APPLE = 0,
+        BANANA = 1,
+    }
+}
+
+declare_binder_union! {
+    Basket(BasketTag) : "a.Basket" {
+        Count(i32) = 0,
+        Labels(Vec<String>) = 1,
+    }
+}
+
+/// A structured parcelable, written with a `sized_write` header like the
+/// AIDL-generated code does.
+#[derive(Debug, Default)]
+struct SizedParcelable {
+    number: i32,
+    text: String,
+}
+
+impl Parcelable for SizedParcelable {
+    fn write_to_parcel(&self, parcel: &mut BorrowedParcel<'_>) -> Result<(), StatusCode> {
+        parcel.sized_write(|subparcel| {
+            subparcel.write(&self.number)?;
+            subparcel.write(&self.text)
+        })
+    }
+
+    fn read_from_parcel(&mut self, parcel: &BorrowedParcel<'_>) -> Result<(), StatusCode> {
+        parcel.sized_read(|subparcel| {
+            if subparcel.has_more_data() {
+                self.number = subparcel.read()?;
+            }
+            if subparcel.has_more_data() {
+                self.text = subparcel.read()?;
+            }
+            Ok(())
+        })
+    }
+}
+
+impl ParcelableMetadata for SizedParcelable {
+    fn get_descriptor() -> &'static str {
+        "a.P"
+    }
+}
+
+impl_serialize_for_parcelable!(SizedParcelable);
+impl_deserialize_for_parcelable!(SizedParcelable);
+
+fn sized_parcelable() -> SizedParcelable {
+    SizedParcelable { number: 7, text: "x".into() }
+}
+
+/// The golden corpus, and the results of checking values against it.
+struct Golden {
+    layouts: BTreeMap<String, Vec<u8>>,
+    checked: RefCell<BTreeSet<String>>,
+    failures: RefCell<Vec<String>>,
+    updates: RefCell<Vec<String>>,
+}
+
+impl Golden {
+    fn parse(corpus: &str) -> Self {
+        let mut layouts = BTreeMap::new();
+        for line in corpus.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (name, data) = line.split_once('=').expect("corpus lines are `name = data`");
+            let hex: String = data.split_whitespace().collect();
+            let bytes = (0..hex.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).expect("invalid hex in corpus"))
+                .collect();
+            assert!(layouts.insert(name.trim().to_string(), bytes).is_none(), "duplicate {}", name);
+        }
+        Self {
+            layouts,
+            checked: RefCell::new(BTreeSet::new()),
+            failures: RefCell::new(Vec::new()),
+            updates: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Check that `value` is written exactly as the golden layout `name`, and
+    /// that reading the golden layout and writing the result gives it back.
+    fn check<T: Serialize + Deserialize>(&self, name: &str, value: &T) {
+        self.checked.borrow_mut().insert(name.to_string());
+
+        let actual = encode(value).expect("failed to write value");
+        let expected = match self.layouts.get(name) {
+            Some(expected) => expected,
+            None => {
+                self.fail(name, "missing from the corpus".into(), &actual);
+                return;
+            }
+        };
+        if actual != *expected {
+            let message = format!("expected {}, got {}", to_hex(expected), to_hex(&actual));
+            self.fail(name, message, &actual);
+            return;
+        }
+
+        let decoded = Parcel::unmarshal(expected).and_then(|parcel| {
+            let value: T = parcel.read()?;
+            if parcel.get_data_position() != parcel.get_data_size() {
+                return Err(StatusCode::BAD_VALUE);
+            }
+            encode(&value)
+        });
+        match decoded {
+            Ok(reencoded) if reencoded == *expected => {}
+            Ok(reencoded) => {
+                let message = format!("re-encoded golden layout as {}", to_hex(&reencoded));
+                self.fail(name, message, &actual);
+            }
+            Err(e) => self.fail(name, format!("failed to read golden layout: {:?}", e), &actual),
+        }
+    }
+
+    fn fail(&self, name: &str, message: String, actual: &[u8]) {
+        self.failures.borrow_mut().push(format!("{}: {}", name, message));
+        self.updates.borrow_mut().push(format!("{} = {}", name, to_hex(actual)));
+    }
+
+    fn finish(self) {
+        let checked = self.checked.into_inner();
+        let mut failures = self.failures.into_inner();
+        failures.extend(
+            self.layouts
+                .keys()
+                .filter(|name| !checked.contains(*name))
+                .map(|name| format!("{}: not checked by any test case", name)),
+        );
+        if failures.is_empty() {
+            return;
+        }
+
+        let mut report = failures.join("\n");
+        let updates = self.updates.into_inner();
+        if !updates.is_empty() {
+            write!(report, "\n\nCorpus lines for the current output:\n{}", updates.join("\n"))
+                .unwrap();
+        }
+        panic!("Golden wire format mismatches:\n{}", report);
+    }
+}
+
+fn encode<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, StatusCode> {
+    let mut parcel = Parcel::new();
+    parcel.write(value)?;
+    parcel.marshal()
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    let words: Vec<String> =
+        bytes.chunks(4).map(|word| word.iter().map(|b| format!("{:02x}", b)).collect()).collect();
+    words.join(" ")
+}
+
+#[test]
+fn golden_wire_format() {
+    let golden = Golden::parse(CORPUS);
+
+    golden.check("bool.true", &true);
+    golden.check("byte.i8", &-1i8);
+    golden.check("char.u16", &(b'A' as u16));
+    golden.check("int.i32", &42i32);
+    golden.check("int.i32_negative", &-2i32);
+    golden.check("int.u32", &0xdeadbeefu32);
+    golden.check("int.i64", &0x0102030405060708i64);
+    golden.check("float.f32", &1.0f32);
+    golden.check("float.f64", &-2.5f64);
+
+    golden.check("string.empty", &String::new());
+    golden.check("string.ascii", &"abc".to_string());
+    golden.check("string.bmp", &"\u{e9}".to_string());
+    golden.check("string.surrogate_pair", &"\u{1f600}".to_string());
+    golden.check("string.null", &None::<String>);
+    golden.check("string16.unpaired_surrogate", &String16::from_units(vec![0xd800, b'a' as u16]));
+
+    golden.check("array.i32", &vec![1i32, -1]);
+    golden.check("array.i32_empty", &Vec::<i32>::new());
+    golden.check("array.i32_null", &None::<Vec<i32>>);
+    golden.check("array.i64", &vec![1i64]);
+    golden.check("array.u8", &vec![1u8, 2, 3]);
+    golden.check("array.u8_null", &None::<Vec<u8>>);
+    golden.check("array.bool", &vec![true, false]);
+    golden.check("array.char", &vec![b'A' as u16, b'B' as u16]);
+    golden.check("array.string", &vec![Some("a".to_string()), None]);
+    golden.check("array.fixed_i32", &[7i32; 2]);
+
+    golden.check("enum.i8", &Fruit::BANANA);
+    golden.check("enum.i8_array", &vec![Fruit::APPLE, Fruit::BANANA]);
+
+    golden.check("parcelable.sized", &sized_parcelable());
+    golden.check("parcelable.null", &None::<SizedParcelable>);
+    golden.check("union.labels", &Basket::Labels(vec!["a".to_string()]));
+
+    golden.check("status.ok", &Status::ok());
+    golden.check("status.exception", &Status::new_exception(ExceptionCode::ILLEGAL_ARGUMENT, None));
+    let message = CString::new("bad").unwrap();
+    golden.check(
+        "status.service_specific",
+        &Status::new_service_specific_error(42, Some(message.as_c_str())),
+    );
+
+    golden.check("time.duration", &Duration::new(5, 250_000_000));
+    golden.check("time.system_time", &(UNIX_EPOCH + Duration::from_secs(1_600_000_000)));
+    golden.check("time.system_time_before_epoch", &(UNIX_EPOCH - Duration::from_millis(1500)));
+
+    let uuid = ParcelUuid::from_u128(0x00112233_4455_6677_8899_aabbccddeeff);
+    golden.check("uuid.parcel_uuid", &uuid);
+
+    golden.check("bundle.empty", &PersistableBundle::new());
+    let mut bundle = PersistableBundle::new();
+    bundle.insert_int("a", 5);
+    golden.check("bundle.int", &bundle);
+
+    golden.check("holder.empty", &ParcelableHolder::new(Default::default()));
+    let mut holder = ParcelableHolder::new(Default::default());
+    holder.set_parcelable(Arc::new(sized_parcelable())).unwrap();
+    golden.check("holder.parcelable", &holder);
+
+    golden.finish();
+}
@@ -952,6 +952,42 @@ mod tests {
         }
     }
 
+    #[test]
+    fn transact_into_reuses_parcels() {
+        let service = BnTest::new_binder(
+            TestService::new("testing_service"),
+            BinderFeatures::default(),
+        );
+        let service = service.as_binder();
+
+        let mut data = service.prepare_transact().unwrap();
+        let mut reply = binder::binder_impl::Parcel::new();
+        for _ in 0..3 {
+            service
+                .transact_into(
+                    super::TestTransactionCode::Test as TransactionCode,
+                    0,
+                    &mut data,
+                    &mut reply,
+                    |_| Ok(()),
+                )
+                .unwrap();
+            assert_eq!(reply.read::<String>().unwrap(), "testing_service");
+        }
+
+        let other = BnTest::new_binder(
+            TestService::new("testing_service2"),
+            BinderFeatures::default(),
+        );
+        let res = other.as_binder().submit_transact_into(
+            super::TestTransactionCode::Test as TransactionCode,
+            &data,
+            &mut reply,
+            0,
+        );
+        assert_eq!(res, Err(binder::StatusCode::BAD_VALUE));
+    }
+
     #[test]
     fn get_is_handling_transaction() {
         let service_name = "get_is_handling_transaction";
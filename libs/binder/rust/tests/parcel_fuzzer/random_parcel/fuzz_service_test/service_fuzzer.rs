@@ -0,0 +1,64 @@
+/*
+ * Copyright (C) 2022 The Android Open Source Project
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Example fuzzer for a Rust binder service, using `fuzz_service`.
+
+#![no_main]
+
+use binder::binder_impl::{BorrowedParcel, TransactionCode};
+use binder::{declare_binder_interface, BinderFeatures, Interface, ParcelFileDescriptor, StatusCode};
+use binder_random_parcel_rs::fuzz_service;
+use libfuzzer_sys::fuzz_target;
+
+pub trait ITestService: Interface {}
+
+declare_binder_interface! {
+    ITestService["android.binder.fuzz.ITestService"] {
+        native: BnTestService(on_transact),
+        proxy: BpTestService,
+    }
+}
+
+struct TestService;
+
+impl Interface for TestService {}
+
+impl ITestService for TestService {}
+
+impl ITestService for BpTestService {}
+
+fn on_transact(
+    _service: &dyn ITestService,
+    code: TransactionCode,
+    data: &BorrowedParcel<'_>,
+    reply: &mut BorrowedParcel<'_>,
+) -> Result<(), StatusCode> {
+    // Exercise the kinds of data that fuzz_service generates: plain data,
+    // binders and file descriptors, and echo them back so that the driver
+    // feeds them into later transactions.
+    match code {
+        1 => reply.write(&data.read::<String>()?),
+        2 => reply.write(&data.read::<Vec<i64>>()?),
+        3 => reply.write(&data.read::<binder::SpIBinder>()?),
+        4 => reply.write(&data.read::<ParcelFileDescriptor>()?),
+        _ => Err(StatusCode::UNKNOWN_TRANSACTION),
+    }
+}
+
+fuzz_target!(|data: &[u8]| {
+    let service = BnTestService::new_binder(TestService, BinderFeatures::default());
+    fuzz_service(&mut service.as_binder(), data);
+});
@@ -0,0 +1,53 @@
+/*
+ * Copyright (C) 2022 The Android Open Source Project
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Fuzzing support for Rust binder services.
+//!
+//! This is the Rust counterpart of the C++ `fuzzService` driver in
+//! `libbinder_random_parcel`, and uses the same implementation so that Rust
+//! and C++ services are fuzzed in the same way.
+
+use binder::unstable_api::AsNative;
+use binder::SpIBinder;
+use binder_random_parcel_bindgen::fuzzRustService;
+use std::os::raw::c_void;
+
+/// Send transactions built from `fuzzer_data` to a local service until the
+/// data is used up.
+///
+/// Each transaction gets an arbitrary code and flags, and a parcel filled with
+/// random data, binders and file descriptors. It starts with the interface
+/// token of the service most of the time, so that the fuzzer gets past the
+/// interface check. Binders and file descriptors returned by the service are
+/// fed back into later transactions, and returned binders are fuzzed as well.
+///
+/// # Example
+///
+/// ```ignore
+/// libfuzzer_sys::fuzz_target!(|data: &[u8]| {
+///     let service = BnFoo::new_binder(FooService::new(), BinderFeatures::default());
+///     binder_random_parcel_rs::fuzz_service(&mut service.as_binder(), data);
+/// });
+/// ```
+pub fn fuzz_service(binder: &mut SpIBinder, fuzzer_data: &[u8]) {
+    let ptr = binder.as_native_mut() as *mut c_void;
+    unsafe {
+        // Safety: `SpIBinder::as_native_mut` returns a valid `AIBinder`
+        // pointer, which we keep a strong reference to for the duration of the
+        // call, and `fuzzer_data` is a valid slice.
+        fuzzRustService(ptr, fuzzer_data.as_ptr(), fuzzer_data.len());
+    }
+}